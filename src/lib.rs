@@ -1,3 +1,6 @@
+// the crate is named after the plugin, which isn't snake case
+#![allow(non_snake_case)]
+
 #[macro_use]
 extern crate vst;
 extern crate queues;
@@ -5,238 +8,6059 @@ extern crate queues;
 use queues::*;
 use vst::host::Host;
 use vst::buffer::AudioBuffer;
-use vst::plugin::{Category, HostCallback, Info, Plugin};
-use vst::api::TimeInfo;
+use vst::plugin::{CanDo, Category, HostCallback, Info, Plugin};
+use vst::api::{Events, Supported, TimeInfo};
+use vst::event::Event;
+
+// the bottom of the Feedback knob. Anything at or below this means no feedback at all
+const MIN_FEEDBACK: f32 = 0.1;
+// the most the loop can feed back with the Feedback knob all the way up, ie. how far below unity it's kept
+// so every repeat is quieter than the last. Only Resonance goes past this (up to RESONANCE_CEILING)
+const FEEDBACK_CEILING: f32 = 0.9;
+
+// Short Delay Guard: below this delay (in seconds) the loop gain is scaled down in proportion to the delay,
+// so 10ms keeps half of it and 5ms a quarter. That's where echoes stop being echoes and the feedback rings as a comb
+const SHORT_DELAY_GUARD_TIME: f32 = 0.02;
+
+// the biggest delay_buffer we'll ever make: 2 seconds at 384kHz, the highest sample rate anyone really uses
+const MAX_DELAY_BUFFER_SIZE: usize = 384000 * 2;
+// how close (in samples) a delay has to be to a whole number of samples to be treated as one, or for long delays
+// that much of the delay (4 in a million), whichever is more. See delay_samples
+const DELAY_SNAP: f32 = 0.001;
+const DELAY_SNAP_RELATIVE: f32 = 0.000004;
+
+// how long (in seconds) a meter holds on to its peak before letting go,
+// and how long it then takes to fall by 60dB
+const METER_HOLD_TIME: f32 = 0.5;
+const METER_RELEASE_TIME: f32 = 1.0;
+// how long a clip light stays on after the last sample over full scale (seconds)
+const CLIP_HOLD_TIME: f32 = 2.0;
+// how many of the most recent diagnostics are kept
+#[cfg(feature = "diagnostics")]
+const DIAGNOSTICS_KEPT: usize = 32;
+// how quickly the CPU usage reading follows changes (seconds), so it can be read by eye rather than jumping
+// about with every block
+#[cfg(feature = "cpu-usage")]
+const CPU_USAGE_SMOOTH_TIME: f32 = 0.5;
+
+// the longest attack (in seconds) the smear can have, at the top of the Attack parameter
+const MAX_ATTACK_TIME: f32 = 0.5;
+// how quickly the smear's two envelope followers react to the wet signal, in seconds
+const SMEAR_FAST_TIME: f32 = 0.001;
+const SMEAR_SLOW_TIME: f32 = 0.05;
+// how far the fast envelope has to jump above the slow one to count as a new echo (about 6dB)
+const SMEAR_THRESHOLD: f32 = 2.0;
+
+// lengths (in seconds) of the decorrelation allpasses. They get rounded up to different primes
+// so the two sides never line up with each other
+const DECORRELATE_TIME_L: f32 = 0.0047;
+const DECORRELATE_TIME_R: f32 = 0.0071;
+const DECORRELATE_GAIN: f32 = 0.6;
+
+// the longest and shortest slice (in seconds) the stutter can repeat, and the most repeats it can do
+const MAX_STUTTER_TIME: f32 = 0.5;
+const MIN_STUTTER_TIME: f32 = 0.01;
+const MAX_STUTTER_REPEATS: f32 = 16.0;
+// fades (in seconds) at the start and end of each slice, and going in and out of the stutter
+const STUTTER_FADE_TIME: f32 = 0.003;
+
+// the shortest and longest loop the looper can record (in seconds), and how far recording runs on past the end
+// of the loop to make the crossfade at its loop point
+const MIN_LOOP_TIME: f32 = 0.5;
+const MAX_LOOP_TIME: f32 = 8.0;
+const LOOP_FADE_TIME: f32 = 0.01;
+
+// how long (in seconds) Freeze takes to go from writing what's coming in to writing back what comes out, and back.
+// Going in, that's the crossfade at the frozen loop's loop point
+const FREEZE_FADE_TIME: f32 = 0.02;
+
+// what slapback mode uses in place of the Delay Time and Dry/Wet knobs (100ms, 35% wet). Feedback is off.
+const SLAPBACK_DELAY_TIME: f32 = 0.05;
+const SLAPBACK_DRY_WET: f32 = 0.35;
+
+// The BBD model's low-pass follows the delay time like a real bucket-brigade chip: the chip has a fixed
+// number of stages, so a longer delay means a slower clock and less bandwidth. With 4096 stages
+// the cutoff is 4096 / (4 * delay), ie. 20kHz at 50ms, 5kHz at 200ms and 512Hz at 2 seconds.
+// It's kept between BBD_MIN_CUTOFF and just under half the sample rate.
+const BBD_STAGES: f32 = 4096.0;
+const BBD_MIN_CUTOFF: f32 = 500.0;
+// how hard the companding squashes loud feedback (x / (1 + amount * |x|))
+const BBD_COMPANDING: f32 = 0.2;
+// loudest the clock noise gets, at the longest delay time (about -70dB)
+const BBD_NOISE_LEVEL: f32 = 0.0003;
+
+// the lo-fi section's range: bits go from MAX_CRUSH_BITS down to MIN_CRUSH_BITS, and the sample rate
+// gets divided by up to MAX_DOWNSAMPLE
+const MAX_CRUSH_BITS: f32 = 16.0;
+const MIN_CRUSH_BITS: f32 = 2.0;
+const MAX_DOWNSAMPLE: f32 = 32.0;
+
+// how long (in seconds) the wet takes to fade out before delay_buffer is cleared
+const CLEAR_FADE_TIME: f32 = 0.03;
+// how long (in seconds) the whole output takes to fade in when the plugin starts or resumes
+const START_FADE_TIME: f32 = 0.005;
+// how long (in seconds) each reversed window fades in and out, so the joins between windows don't click
+const REVERSE_FADE_TIME: f32 = 0.005;
+
+// the slowest and fastest the tremolo goes (in Hz)
+const MIN_TREMOLO_RATE: f32 = 0.1;
+const MAX_TREMOLO_RATE: f32 = 20.0;
+
+// Mix Lock: how long (in seconds) the level matching averages over, how far it can push the wet
+// either way (12dB), and how quiet the input can get before it stops adjusting (about -60dB)
+const MIX_LOCK_TIME: f32 = 0.5;
+const MIX_LOCK_MAX_GAIN: f32 = 4.0;
+const MIX_LOCK_SILENCE: f32 = 0.000001;
+
+// where Mono Output's lo-fi setting rolls the top end off (in Hz), about like AM radio
+const MONO_LOFI_CUTOFF: f32 = 4500.0;
+
+// the most feedback Resonance can push the loop to. Just under 1, so it sustains for ages but does die away eventually
+const RESONANCE_CEILING: f32 = 0.999;
+// where the loop's limiter starts to bend (with Resonance up), well below full scale so it eases in
+const RESONANCE_KNEE: f32 = 0.5;
+
+// the built in test signal: a sine at TEST_TONE_FREQ (Hz), or a click every TEST_IMPULSE_TIME (seconds),
+// both at TEST_SIGNAL_LEVEL (-12dB)
+const TEST_TONE_FREQ: f32 = 440.0;
+const TEST_IMPULSE_TIME: f32 = 1.0;
+const TEST_SIGNAL_LEVEL: f32 = 0.25;
+
+// the grids (in ms) Delay Quantize can snap the delay time to. The first one is off
+const QUANTIZE_GRIDS: [f32; 6] = [0.0, 1.0, 5.0, 10.0, 50.0, 100.0];
+
+// With Delay in Hz on, the Delay Time knob goes from one to the other (Hz). The top is the shortest delay there is, 0.1ms
+const TUNED_LOWEST: f32 = 20.0;
+const TUNED_HIGHEST: f32 = 10000.0;
+
+// String Mode's loop gain, in place of Feedback. Just under 1, so a pluck rings on and String Damping decides for how long
+const STRING_LOOP_GAIN: f32 = 0.998;
+// where String Mode's low-pass in the loop sits (Hz), from String Damping at 0% to 100%
+const STRING_BRIGHTEST: f32 = 18000.0;
+const STRING_DARKEST: f32 = 1000.0;
+
+// where the output's subsonic filter cuts in (Hz)
+const SUBSONIC_CUTOFF: f32 = 20.0;
+
+// how far (in seconds) Stereo pushes the right side of the wet behind the left at 100%.
+// Short enough to be heard as width rather than a separate echo
+const STEREO_MAX_OFFSET: f32 = 0.015;
+// how much Stereo turns the side up at 100%, on top of the offset
+const STEREO_MAX_WIDEN: f32 = 0.5;
+
+// Motion: the furthest (in seconds) each side's read of the delay line wanders from where it would be, at 100%
+const MOTION_MAX_OFFSET: f32 = 0.003;
+// how often (in seconds) Motion picks somewhere new to wander to, from 0% up to 100%
+const MOTION_SLOWEST: f32 = 2.0;
+const MOTION_FASTEST: f32 = 0.5;
+// how long (in seconds) it takes to drift most of the way there
+const MOTION_GLIDE_TIME: f32 = 1.0;
+// where each side's random wandering starts from. Any nonzero numbers would do, as long as they're different
+const DRIFT_SEED_L: u32 = 0x2545_f491;
+const DRIFT_SEED_R: u32 = 0x9e37_79b9;
+
+// Scatter: the most of the delay (at 100%) a repeat can come back early by
+const SCATTER_MAX_SHARE: f32 = 0.5;
+// how long (in seconds) it takes to crossfade from one place to read to the next
+const SCATTER_FADE_TIME: f32 = 0.005;
+// how many different Scatter Seeds the knob goes through
+const SCATTER_SEEDS: f32 = 100.0;
+
+// Feedback quieter than this (about -300dB) is flushed to 0, before it gets anywhere near denormal
+const DENORMAL_FLUSH_LEVEL: f32 = 1e-15;
+// or, with Denormals set to Dither, the level of the noise added to it instead (-140dB)
+const DENORMAL_DITHER_LEVEL: f32 = 1e-7;
+const DITHER_SEED: u32 = 0x6c07_8965;
+
+// the biggest step Pan Rotate takes per echo, a quarter turn (90 degrees)
+const PAN_ROTATE_MAX_STEP: f32 = std::f32::consts::FRAC_PI_2;
+
+// Natural Damping: where the low-pass in the loop sits (Hz) with the Feedback knob at the bottom and at the top.
+// It only ever moves down from the brightest, so a little feedback sounds the same as without it
+const DAMPING_BRIGHTEST: f32 = 18000.0;
+const DAMPING_DARKEST: f32 = 4000.0;
+
+// Age, one knob for "make it sound old". As it goes up, everything coming out of the buffer goes through a low-pass
+// that falls from AGE_BRIGHTEST to AGE_DARKEST (Hz) and picks up hiss up to AGE_NOISE_LEVEL (-60dB), the feedback
+// gets soft clipped (all the way to tanh at 100%), and up to AGE_MAX_MOTION is added on top of the Motion knob for
+// wow and flutter. At 0 none of it happens. It doesn't move the other knobs, it works alongside them
+const AGE_BRIGHTEST: f32 = 16000.0;
+const AGE_DARKEST: f32 = 2500.0;
+const AGE_NOISE_LEVEL: f32 = 0.001;
+const AGE_MAX_MOTION: f32 = 0.5;
+const AGE_NOISE_SEED: u32 = 0x85eb_ca6b;
+
+// Which MIDI CC moves which parameter, on any channel. 12 and 13 are the standard Effect Control 1 and 2,
+// and 91 is usually the effects send, which is near enough to Dry/Wet. 80 is a general purpose switch, for a footswitch
+// to start the looper recording. The CC's 0 to 127 becomes the usual 0 to 1
+const MIDI_CC_MAP: [(u8, Param); 4] = [
+    (12, Param::DelayTime),
+    (13, Param::Feedback),
+    (80, Param::LoopRecord),
+    (91, Param::DryWet),
+];
+
+// how many held notes Note Delay remembers, so letting go of one goes back to the one before.
+// Past this the oldest is forgotten
+const MAX_HELD_NOTES: usize = 16;
 
-// used to make feedback reduce in volume each iteration
-const FEEDBACK_FACTOR: f32 = 0.1;
+// how much of each side's feedback crosses over to the other in the Cross-Linked channel mode
+const CROSS_LINK_AMOUNT: f32 = 0.25;
+
+// the longest lookahead (in seconds) the ducking can use
+const MAX_LOOKAHEAD_TIME: f32 = 0.01;
+// how quickly the ducking reacts to the input getting louder, and lets go once it's quieter (in seconds)
+const DUCK_ATTACK_TIME: f32 = 0.001;
+const DUCK_RELEASE_TIME: f32 = 0.1;
+
+// how long (in seconds) the correlation reading averages over
+const CORRELATION_TIME: f32 = 0.3;
+// how long (in seconds) Mono Safe takes to go all the way from full width to mono, or back
+const MONO_SAFE_TIME: f32 = 0.5;
+
+// the range (in Hz) Phase Rotate sweeps its allpass over, from a touch of rotation at the top to lots at the bottom
+const PHASE_ROTATE_MAX_FREQ: f32 = 5000.0;
+const PHASE_ROTATE_MIN_FREQ: f32 = 200.0;
+
+// the most echoes Repeat Limit can be set to (one step further is no limit)
+const MAX_REPEAT_LIMIT: f32 = 16.0;
+// below this the input counts as silent, as far as Repeat Limit is concerned (about -80dB)
+const REPEAT_LIMIT_SILENCE: f32 = 0.0001;
+
+// how far (in dB) the input and output trims go either way
+const TRIM_RANGE_DB: f32 = 24.0;
+
+// Goes up whenever a parameter's value starts meaning something different. 2 is when the Delay Time knobs
+// went logarithmic. The host is told (see get_info), and export_settings puts it in the first line
+const PARAMETER_VERSION: i32 = 2;
+// the first line of export_settings from before there was a version, when the Delay Time knobs were linear
+const SETTINGS_HEADER_V1: &str = "# SillyDelay settings";
 
 // define the struct for the plugin
-struct SillyDelay {
+pub struct SillyDelay {
     delay_time: f32,
+    // where the Delay Time knob is, 0 to 1 as the host sees it. delay_time is worked out from it (see knob_to_delay),
+    // and this is what goes back to the host, so it gets back exactly what it sent
+    delay_knob: f32,
     dry_wet: f32,
     sample_rate: f32,
-    // CircularBuffer is explained later. It will hold a left channel and a right channel, hence the tuple.
-    delay_buffer: CircularBuffer::<(f32, f32)>,
+    // DelayLine is explained later. It holds a left channel and a right channel, hence the tuple.
+    delay_buffer: DelayLine,
     feedback_amt: f32,
+    // flips the polarity of the dry signal before it's mixed with the wet
+    invert_dry: bool,
+    // how long each echo takes to fade in, 0 to 1 (0 being no fade at all)
+    attack: f32,
+    smear: Smear,
+    // how much of the decorrelated wet to use, 0 to 1
+    decorrelate: f32,
+    allpass_l: Allpass,
+    allpass_r: Allpass,
+    // run the delay line and feedback on mid and side instead of left and right
+    mid_side: bool,
+    // Whether what's in delay_buffer is mid/side. It only follows mid_side_on() when a Clear swaps the
+    // buffer, so what's fading out on the way there is still read back the way it was written
+    buffer_mid_side: bool,
+    // the stutter trigger as the host last set it, so we only start on the way up
+    stutter_trigger: bool,
+    // slice length and repeat count, 0 to 1
+    stutter_length: f32,
+    stutter_repeats: f32,
+    stutter: Stutter,
+    // the looper's trigger as the host last set it (like the stutter's), the length of the next recording (0 to 1,
+    // see loop_time), whether to overdub and how loud the loop plays
+    loop_trigger: bool,
+    loop_length: f32,
+    loop_overdub: bool,
+    loop_level: f32,
+    looper: Looper,
+    // how far the wet gets turned down when the input is loud, 0 to 1
+    duck_depth: f32,
+    ducker: Ducker,
+    // what the ducker listens to, see DuckSource
+    duck_source: DuckSource,
+    last_output: (f32, f32),
+    last_dry: (f32, f32),
+    // where the feedback comes from, see Param::FeedbackTap. last_mix is the dry/wet mix from the sample before
+    post_mix_feedback: bool,
+    last_mix: (f32, f32),
+    // Freeze holds whatever's in the buffer, see where freeze_mix is used. freeze_mix goes from 0 (not frozen)
+    // to 1 (frozen) over FREEZE_FADE_TIME
+    freeze: bool,
+    freeze_mix: f32,
+    // how far ahead the ducking looks, 0 to 1 of MAX_LOOKAHEAD_TIME. Everything else runs this late.
+    lookahead_time: f32,
+    lookahead_buffer: CircularBuffer<(f32, f32)>,
+    // what happens to the input channels before anything else
+    input_routing: InputRouting,
+    // the Clear trigger as the host last set it
+    clear_trigger: bool,
+    // fresh, empty delay_buffer and network_buffer waiting for the wet to finish fading out before they're swapped in
+    pending_buffers: Option<(DelayLine, DelayLine)>,
+    // how much of the wet we hear, 0 to 1, and how far it moves each sample while fading
+    clear_gain: f32,
+    clear_step: f32,
+    // the same for the whole output, which fades in from silence after new and resume
+    start_gain: f32,
+    start_step: f32,
+    // a single short echo, overriding delay time, feedback and dry/wet while it's on
+    slapback: bool,
+    // the analog bucket-brigade model, on or off
+    bbd_on: bool,
+    bbd: Bbd,
+    // lo-fi amounts, 0 to 1. 0 is untouched for both
+    bits: f32,
+    downsample: f32,
+    crusher: Crusher,
+    // Mono Safe narrows the wet when it gets too far out of phase. The floor is 0 to 1, ie. a correlation of -1 to 1
+    mono_safe: bool,
+    correlation_floor: f32,
+    width: Width,
+    // 0 to 1, 0 being off. Runs the right side of the wet through an allpass, see phase_rotate_freq
+    phase_rotate: f32,
+    phase_rotator: Biquad,
+    // 0 to 1, see repeat_limit. since_input counts samples since the input was last above REPEAT_LIMIT_SILENCE
+    repeat_limit: f32,
+    since_input: usize,
+    // plain level adjustments on the way in (before everything, dry included) and on the way out (after everything).
+    // 0 to 1 like the host sees them, plus the linear gain so process doesn't have to work it out
+    input_trim: f32,
+    input_gain: f32,
+    output_trim: f32,
+    output_gain: f32,
+    // plays every repeat after the first one backwards
+    reverse_feedback: bool,
+    reverser: Reverser,
+    // 0 is hard left, 1 hard right. Only the dry moves, plus the left and right gains that does it (see pan_law)
+    dry_pan: f32,
+    dry_pan_gains: (f32, f32),
+    // turns the wet up and down in a cycle. Rate is 0 to 1 (see tremolo_rate_hz), phase is how far through
+    // the cycle it is (0 to 1). Free running only, there's no tempo sync
+    tremolo_rate: f32,
+    tremolo_depth: f32,
+    tremolo_shape: TremoloShape,
+    tremolo_phase: f32,
+    // keeps the output level the same wherever Dry/Wet is, see LevelMatch and mix_samples_locked
+    mix_lock: bool,
+    level_match: LevelMatch,
+    saturation: Saturation,
+    // Normally the smear, ducker, Repeat Limit and Mix Lock each listen to both sides at once and do the same
+    // to both, so something loud on one side moves the other. True Stereo gives the right side its own copy
+    // of each (these, the left side uses the usual ones) so the two sides don't affect each other at all.
+    // The things that mix the sides on purpose (mid/side, mono input routing, Mono Safe) still do
+    true_stereo: bool,
+    smear_r: Smear,
+    ducker_r: Ducker,
+    since_input_r: usize,
+    level_match_r: LevelMatch,
+    // sums the finished output (dry and wet) to mono, right at the end
+    mono_output: MonoOutput,
+    // which side the dry and the wet come out of, see OutputRouting
+    output_routing: OutputRouting,
+    // Dual Mono, Cross-Linked or Mid/Side, see ChannelMode. Mid/Side here and Stereo Mode's Mid/Side are the same thing,
+    // either one turns it on. True Stereo is separate: it's about the detectors, not the audio
+    channel_mode: ChannelMode,
+    // only delay the side: runs on mid and side like Mid/Side, but the mid never goes into the buffer,
+    // so whatever's in the centre (vocals, bass) stays dry and only the stereo ambience echoes
+    side_only: bool,
+    // every parameter's value, as the host sees them, when store_snapshot was last called for A and B
+    snapshots: [Option<[f32; Param::ALL.len()]>; 2],
+    // 0 is all A, 1 is all B
+    morph: f32,
+    mono_filter: Biquad,
+    // 0 to 1, takes the feedback from wherever the Feedback knob has it up to RESONANCE_CEILING
+    resonance: f32,
+    // For checking the plugin works (or reproducing a bug report) without having to feed it anything.
+    // While it's on the input is replaced completely. test_phase is how far through the sine's cycle
+    // it is (0 to 1), test_countdown is samples until the next click
+    test_signal: TestSignal,
+    test_phase: f32,
+    test_countdown: usize,
+    // 0 to 1, picks one of QUANTIZE_GRIDS for the delay time to snap to (see current_delay_time)
+    quantize: f32,
+    // high-passes the final output to get rid of rumble and DC, from the dry or built up in the loop
+    subsonic: bool,
+    subsonic_l: Biquad,
+    subsonic_r: Biquad,
+    // one knob for instant stereo: offsets the right side of the wet and widens it, both at once.
+    // Decorrelate and the rest still work on their own
+    stereo: f32,
+    spread: Spread,
+    // slow random wandering of where each side reads the delay line from, see Drift
+    motion: f32,
+    drift_l: Drift,
+    drift_r: Drift,
+    // 0 to 1, how far each repeat can come back early, picked at random, see Scatter. The seed is the knob
+    // as it was set, see scatter_seed
+    scatter: f32,
+    scatter_seed: f32,
+    scatterer: Scatter,
+    // As a tail dies away the feedback gets quieter and quieter until it's denormal, and on a lot of CPUs
+    // the maths on those is many times slower. Off flushes the feedback to 0 once it's below
+    // DENORMAL_FLUSH_LEVEL. On adds noise at DENORMAL_DITHER_LEVEL instead, so it never gets that quiet,
+    // and the tail fades into a (silent) noise floor rather than stopping dead
+    denormal_dither: bool,
+    dither_state: u32,
+    // 0 to 1, how far round the stereo field each echo moves from the one before, up to PAN_ROTATE_MAX_STEP
+    pan_rotate: f32,
+    interpolation: Interpolation,
+    // a low-pass on the feedback that gets darker as Feedback goes up, see damping_cutoff
+    natural_damping: bool,
+    damping_l: Biquad,
+    damping_r: Biquad,
+    // 0 to 1, see AGE_BRIGHTEST
+    age: f32,
+    age_filter_l: Biquad,
+    age_filter_r: Biquad,
+    age_noise_state: u32,
+    // stops the delay running at all while Dry/Wet is at 0, see idle
+    idle_when_dry: bool,
+    // turns the feedback down at very short delays, see short_delay_gain
+    short_delay_guard: bool,
+    // MIDI notes set the delay time, see note_delay_time. The notes are kept track of whether it's on or not
+    note_delay: bool,
+    held_notes: HeldNotes,
+    // the Delay Time knob picks a pitch instead of a time, see hz_delay_time
+    delay_in_hz: bool,
+    // Karplus-Strong, see string_filter. string_state is where each side's one-pole low-pass has got to
+    string_mode: bool,
+    string_damping: f32,
+    string_state: (f32, f32),
+    // Network mode adds a second delay line, cross-coupled with delay_buffer (see process)
+    network: bool,
+    delay_time_2: f32,
+    delay_knob_2: f32,
+    network_buffer: DelayLine,
+    // the biggest block the host has said it will send. 0 if it hasn't told us.
+    block_size: usize,
+    // somewhere to put a block worth of samples (one per channel) without allocating during process
+    scratch_l: Vec<f32>,
+    scratch_r: Vec<f32>,
+    // the same for the sidechain, and whether this block actually came with one
+    sidechain_l: Vec<f32>,
+    sidechain_r: Vec<f32>,
+    has_sidechain: bool,
+    // peak meters for the three places level can change: going in, coming out of the delay, and the final mix
+    input_meter: PeakMeter,
+    wet_meter: PeakMeter,
+    output_meter: PeakMeter,
+    #[cfg(feature = "diagnostics")]
+    diagnostics: Diagnostics,
+    #[cfg(feature = "cpu-usage")]
+    cpu_meter: CpuMeter,
+    // called after every parameter change, for code that embeds the plugin (see set_parameter_listener)
+    parameter_listener: Option<Box<dyn FnMut(Param, f32) + Send>>,
+    // The host, for telling it when a MIDI CC has moved a knob. Only new() has a real one: a default
+    // HostCallback isn't connected to anything and crashes if it's used, so the rest get None
+    host: Option<HostCallback>,
 }
 
-impl Default for SillyDelay {
+// a snapshot of the meters, for a GUI (someday) or for debugging.
+// values are linear peak levels, so 1.0 is full scale
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Meters {
+    pub input: f32,
+    pub wet: f32,
+    pub output: f32,
+    // how alike the two sides of the wet are: 1 is mono, 0 is unrelated, -1 cancels out completely in mono
+    pub correlation: f32,
+}
 
-    // This is somehow necessary, but doesn't really do much since we initialize later anyway
-    fn default() -> SillyDelay {
-        SillyDelay {
-            delay_buffer: reload_delay_buffer(44100., 0.001),
-            delay_time: 0.001,
-            dry_wet: 1.0,
-            sample_rate: 44100.,
-            feedback_amt: 0.1,
+// Every parameter the host can see. Param::ALL is the one place the order is decided: a parameter's index
+// (what the host calls it) is its position in there, so get_info's count and every match below
+// always agree with each other.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Param {
+    DelayTime,
+    Feedback,
+    DryWet,
+    DryPolarity,
+    Attack,
+    Decorrelate,
+    StereoMode,
+    Stutter,
+    StutterLength,
+    StutterRepeats,
+    DuckDepth,
+    Lookahead,
+    InputRouting,
+    Clear,
+    Slapback,
+    Bbd,
+    Bits,
+    Downsample,
+    Network,
+    DelayTime2,
+    MonoSafe,
+    CorrelationFloor,
+    PhaseRotate,
+    RepeatLimit,
+    InputTrim,
+    OutputTrim,
+    ReverseFeedback,
+    DryPan,
+    TremoloRate,
+    TremoloDepth,
+    TremoloShape,
+    MixLock,
+    Saturation,
+    TrueStereo,
+    MonoOutput,
+    Resonance,
+    TestSignal,
+    Quantize,
+    Subsonic,
+    Stereo,
+    ChannelMode,
+    SideOnly,
+    Morph,
+    DuckSource,
+    Motion,
+    Denormals,
+    PanRotate,
+    Interpolation,
+    NaturalDamping,
+    Age,
+    IdleWhenDry,
+    ShortDelayGuard,
+    LoopRecord,
+    LoopLength,
+    LoopOverdub,
+    LoopLevel,
+    FeedbackTap,
+    NoteDelay,
+    OutputRouting,
+    Freeze,
+    Scatter,
+    ScatterSeed,
+    DelayInHz,
+    StringMode,
+    StringDamping,
+}
+
+impl Param {
+    pub const ALL: [Param; 65] = [
+        Param::DelayTime,
+        Param::Feedback,
+        Param::DryWet,
+        Param::DryPolarity,
+        Param::Attack,
+        Param::Decorrelate,
+        Param::StereoMode,
+        Param::Stutter,
+        Param::StutterLength,
+        Param::StutterRepeats,
+        Param::DuckDepth,
+        Param::Lookahead,
+        Param::InputRouting,
+        Param::Clear,
+        Param::Slapback,
+        Param::Bbd,
+        Param::Bits,
+        Param::Downsample,
+        Param::Network,
+        Param::DelayTime2,
+        Param::MonoSafe,
+        Param::CorrelationFloor,
+        Param::PhaseRotate,
+        Param::RepeatLimit,
+        Param::InputTrim,
+        Param::OutputTrim,
+        Param::ReverseFeedback,
+        Param::DryPan,
+        Param::TremoloRate,
+        Param::TremoloDepth,
+        Param::TremoloShape,
+        Param::MixLock,
+        Param::Saturation,
+        Param::TrueStereo,
+        Param::MonoOutput,
+        Param::Resonance,
+        Param::TestSignal,
+        Param::Quantize,
+        Param::Subsonic,
+        Param::Stereo,
+        Param::ChannelMode,
+        Param::SideOnly,
+        Param::Morph,
+        Param::DuckSource,
+        Param::Motion,
+        Param::Denormals,
+        Param::PanRotate,
+        Param::Interpolation,
+        Param::NaturalDamping,
+        Param::Age,
+        Param::IdleWhenDry,
+        Param::ShortDelayGuard,
+        Param::LoopRecord,
+        Param::LoopLength,
+        Param::LoopOverdub,
+        Param::LoopLevel,
+        Param::FeedbackTap,
+        Param::NoteDelay,
+        Param::OutputRouting,
+        Param::Freeze,
+        Param::Scatter,
+        Param::ScatterSeed,
+        Param::DelayInHz,
+        Param::StringMode,
+        Param::StringDamping,
+    ];
+
+    // None for anything the host asks about that isn't a parameter
+    pub fn from_i32(index: i32) -> Option<Param> {
+        if index < 0 {
+            return None;
+        }
+        Param::ALL.get(index as usize).copied()
+    }
+
+    // the host's index for this parameter
+    pub fn to_i32(self) -> i32 {
+        Param::ALL.iter().position(|&param| param == self).unwrap() as i32
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Param::DelayTime => "Delay Time",
+            Param::Feedback => "Feedback",
+            Param::DryWet => "Dry/Wet",
+            Param::DryPolarity => "Dry Polarity",
+            Param::Attack => "Attack",
+            Param::Decorrelate => "Decorrelate",
+            Param::StereoMode => "Stereo Mode",
+            Param::Stutter => "Stutter",
+            Param::StutterLength => "Stutter Length",
+            Param::StutterRepeats => "Stutter Repeats",
+            Param::DuckDepth => "Duck Depth",
+            Param::Lookahead => "Lookahead",
+            Param::InputRouting => "Input Routing",
+            Param::Clear => "Clear",
+            Param::Slapback => "Slapback",
+            Param::Bbd => "Analog BBD",
+            Param::Bits => "Bits",
+            Param::Downsample => "Downsample",
+            Param::Network => "Network",
+            Param::DelayTime2 => "Delay Time 2",
+            Param::MonoSafe => "Mono Safe",
+            Param::CorrelationFloor => "Correlation Floor",
+            Param::PhaseRotate => "Phase Rotate",
+            Param::RepeatLimit => "Repeat Limit",
+            Param::InputTrim => "Input Trim",
+            Param::OutputTrim => "Output Trim",
+            Param::ReverseFeedback => "Reverse Feedback",
+            Param::DryPan => "Dry Pan",
+            Param::TremoloRate => "Tremolo Rate",
+            Param::TremoloDepth => "Tremolo Depth",
+            Param::TremoloShape => "Tremolo Shape",
+            Param::MixLock => "Mix Lock",
+            Param::Saturation => "Saturation",
+            Param::TrueStereo => "True Stereo",
+            Param::MonoOutput => "Mono Output",
+            Param::Resonance => "Resonance",
+            Param::TestSignal => "Test Signal",
+            Param::Quantize => "Delay Quantize",
+            Param::Subsonic => "Subsonic Filter",
+            Param::Stereo => "Stereo",
+            Param::ChannelMode => "Channel Mode",
+            Param::SideOnly => "Side Only",
+            Param::Morph => "Morph",
+            Param::DuckSource => "Duck Source",
+            Param::Motion => "Motion",
+            Param::Denormals => "Denormals",
+            Param::PanRotate => "Pan Rotate",
+            Param::Interpolation => "Interpolation",
+            Param::NaturalDamping => "Natural Damping",
+            Param::Age => "Age",
+            Param::IdleWhenDry => "Idle When Dry",
+            Param::ShortDelayGuard => "Short Delay Guard",
+            Param::LoopRecord => "Loop Record",
+            Param::LoopLength => "Loop Length",
+            Param::LoopOverdub => "Loop Overdub",
+            Param::LoopLevel => "Loop Level",
+            Param::FeedbackTap => "Feedback Tap",
+            Param::NoteDelay => "Note Delay",
+            Param::OutputRouting => "Output Routing",
+            Param::Freeze => "Freeze",
+            Param::Scatter => "Scatter",
+            Param::ScatterSeed => "Scatter Seed",
+            Param::DelayInHz => "Delay in Hz",
+            Param::StringMode => "String Mode",
+            Param::StringDamping => "String Damping",
+        }
+    }
+
+    // the unit shown after the value text, if there is one
+    pub fn label(self) -> &'static str {
+        match self {
+            Param::DelayTime => "ms",
+            Param::Feedback => "%",
+            Param::DryWet => "%",
+            Param::Attack => "ms",
+            Param::Decorrelate => "%",
+            Param::StutterLength => "ms",
+            Param::DuckDepth => "%",
+            Param::Lookahead => "ms",
+            Param::Downsample => "x",
+            Param::DelayTime2 => "ms",
+            Param::PhaseRotate => "Hz",
+            Param::InputTrim | Param::OutputTrim => "dB",
+            Param::TremoloRate => "Hz",
+            Param::TremoloDepth => "%",
+            Param::Resonance => "%",
+            Param::Quantize => "ms",
+            Param::Stereo => "%",
+            Param::Morph => "%",
+            Param::Motion => "%",
+            Param::PanRotate => "deg",
+            Param::Age => "%",
+            Param::LoopLength => "s",
+            Param::LoopLevel => "%",
+            Param::Scatter => "%",
+            Param::StringDamping => "%",
+            _ => "",
+        }
+    }
+
+    // the value (0 to 1, like the host sees it) the plugin starts with
+    pub fn default(self) -> f32 {
+        match self {
+            // 2ms and 60ms
+            Param::DelayTime => delay_to_knob(0.001),
+            Param::Feedback => 0.1,
+            Param::DryWet => 1.0,
+            Param::StutterLength => 0.25,
+            Param::StutterRepeats => 0.2,
+            Param::DelayTime2 => delay_to_knob(0.03),
+            // a correlation of 0
+            Param::CorrelationFloor => 0.5,
+            // no limit
+            Param::RepeatLimit => 1.0,
+            // 0dB
+            Param::InputTrim | Param::OutputTrim => 0.5,
+            // centre
+            Param::DryPan => 0.5,
+            // about 1.4Hz
+            Param::TremoloRate => 0.5,
+            // linear
+            Param::Interpolation => 0.5,
+            // 2 seconds
+            Param::LoopLength => 0.2,
+            // nothing plays until something's been recorded, so it can start at full level
+            Param::LoopLevel => 1.0,
+            // about 4kHz, a nylon-ish string rather than a wire
+            Param::StringDamping => 0.5,
+            _ => 0.0,
+        }
+    }
+
+    // the lowest and highest values set_parameter will actually keep. Everything takes 0 to 1
+    // from the host, but delay time can't be 0 and anything below 0.1 feedback is the same as 0.1.
+    // The shortest delay is 0.1ms, for flanger and comb filter sounds. For the delay times it's the delay
+    // itself (0 to 1 of 2000ms), which the knob sweeps across logarithmically, see knob_to_delay
+    pub fn range(self) -> (f32, f32) {
+        match self {
+            Param::DelayTime | Param::DelayTime2 => (0.00005, 1.0),
+            Param::Feedback => (MIN_FEEDBACK, 1.0),
+            _ => (0.0, 1.0),
+        }
+    }
+
+    // Somewhere worth snapping to on the way past (0 to 1, like the host sees it), for a GUI or a host that does detents.
+    // VST 2 has no way of telling the host, so for now it's only here for code that embeds the plugin. It's not
+    // the same as default(): Dry/Wet starts fully wet, but halfway is the even mix people look for.
+    // None for knobs with nothing in the middle worth stopping at, and for switches and selectors, which only have steps
+    pub fn detent(self) -> Option<f32> {
+        match self {
+            // an even mix
+            Param::DryWet => Some(0.5),
+            // no feedback, the bottom of its range
+            Param::Feedback => Some(MIN_FEEDBACK),
+            // 0dB
+            Param::InputTrim | Param::OutputTrim => Some(0.5),
+            // centre
+            Param::DryPan => Some(0.5),
+            // a correlation of 0
+            Param::CorrelationFloor => Some(0.5),
+            // halfway between A and B
+            Param::Morph => Some(0.5),
+            _ => None,
+        }
+    }
+
+    // whether Morph moves this one. Only the knobs that can glide: switches and selectors have nothing in between,
+    // and anything that resizes a buffer (delay times, Lookahead, Quantize) would clear it on every step
+    fn morphs(self) -> bool {
+        matches!(self,
+            Param::Feedback | Param::DryWet | Param::Attack | Param::Decorrelate | Param::StutterLength
+            | Param::StutterRepeats | Param::DuckDepth | Param::Bits | Param::Downsample | Param::CorrelationFloor
+            | Param::PhaseRotate | Param::RepeatLimit | Param::InputTrim | Param::OutputTrim | Param::DryPan
+            | Param::TremoloRate | Param::TremoloDepth | Param::Resonance | Param::Stereo | Param::Motion
+            | Param::PanRotate | Param::Age | Param::LoopLevel | Param::Scatter | Param::StringDamping)
+    }
+}
+
+// the choices for the Input Routing parameter
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputRouting {
+    // left is left, right is right
+    Normal,
+    // left and right swap places
+    Swap,
+    // both sides get (left + right) / 2, for sources that are really mono anyway
+    Mono,
+}
+
+impl InputRouting {
+    // the host gives us 0 to 1, split into three equal steps
+    fn from_param(value: f32) -> InputRouting {
+        match (value * 2.0).round() as i32 {
+            1 => InputRouting::Swap,
+            2 => InputRouting::Mono,
+            _ => InputRouting::Normal,
+        }
+    }
+
+    fn to_param(self) -> f32 {
+        match self {
+            InputRouting::Normal => 0.0,
+            InputRouting::Swap => 0.5,
+            InputRouting::Mono => 1.0,
+        }
+    }
+
+    fn route(self, left: f32, right: f32) -> (f32, f32) {
+        match self {
+            InputRouting::Normal => (left, right),
+            InputRouting::Swap => (right, left),
+            InputRouting::Mono => ((left + right) * 0.5, (left + right) * 0.5),
         }
     }
 }
 
-// implement Plugin for SillyDelay
-impl Plugin for SillyDelay {
+// the choices for the Tremolo Shape parameter
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TremoloShape {
+    Sine,
+    Triangle,
+    // with the corners rounded off a little, a true square would click on every edge
+    Square,
+}
 
-    // initialize
-    fn new(host: HostCallback) -> Self {
+impl TremoloShape {
+    // the host gives us 0 to 1, split into three equal steps
+    fn from_param(value: f32) -> TremoloShape {
+        match (value * 2.0).round() as i32 {
+            1 => TremoloShape::Triangle,
+            2 => TremoloShape::Square,
+            _ => TremoloShape::Sine,
+        }
+    }
 
-        // Get the sample rate immediately before anything else
-        // In order to set the sample rate in the case that it's not changed
-        // use get_time_info with no flags. Sample rate is always valid in TimeInfo
-        // Possible improvement: set Tempo flag and use Tempo with sample rate for Synced delay times.
-        let sample_rate = if let Some(time_info) = host.get_time_info(0) {
-            match time_info {
-                TimeInfo { sample_rate, ..} => sample_rate as f32
-            }
-            // will never get to else clause
-        } else { 0.0 };
+    fn to_param(self) -> f32 {
+        match self {
+            TremoloShape::Sine => 0.0,
+            TremoloShape::Triangle => 0.5,
+            TremoloShape::Square => 1.0,
+        }
+    }
 
-        SillyDelay {
-            delay_time: 0.001,
-            dry_wet: 1.0,
-            sample_rate: sample_rate,
-            delay_buffer: reload_delay_buffer(sample_rate, 0.001),
-            feedback_amt: 0.1,
+    // the shape at a point in its cycle (phase is 0 to 1), from -1 to 1
+    fn value(self, phase: f32) -> f32 {
+        let sine = (phase * 2.0 * std::f32::consts::PI).sin();
+        match self {
+            TremoloShape::Sine => sine,
+            // lined up with the sine, so it peaks at a quarter of the way through
+            TremoloShape::Triangle => 4.0 * ((phase - 0.25).rem_euclid(1.0) - 0.5).abs() - 1.0,
+            TremoloShape::Square => (sine * 8.0).clamp(-1.0, 1.0),
         }
     }
+}
 
-    // necessary for Plugin trait
-    fn get_info(&self) -> Info {
-        Info { 
-            parameters: 3,
-            inputs: 2,
-            outputs: 2,
-            category: Category::Effect,
-            f64_precision: false,
+// the choices for the Saturation parameter, ie. what the feedback goes through on its way back into the buffer.
+// All of them leave quiet signals (well below full scale) more or less alone and only bite on loud repeats,
+// so with a lot of feedback each repeat gets a bit more squashed instead of just louder
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Saturation {
+    Off,
+    // tanh. Smooth and gradual, the closest to clean while it's quiet
+    Soft,
+    // straight clipping at full scale. Clean right up to it, then harsh and buzzy
+    Hard,
+    // anything over full scale gets folded back down instead of flattened. Bright and metallic when pushed
+    Fold,
+    // lopsided, like a diode: the negative half clips at half the level of the positive half.
+    // That adds even harmonics, which sounds warmer and thicker
+    Diode,
+}
 
-            name: "SillyDelay".to_string(),
-            vendor: "Volfym".to_string(),
-            // randomly generated online. necessary to work
-            unique_id: 486893,
+impl Saturation {
+    // the host gives us 0 to 1, split into five equal steps
+    fn from_param(value: f32) -> Saturation {
+        match (value * 4.0).round() as i32 {
+            1 => Saturation::Soft,
+            2 => Saturation::Hard,
+            3 => Saturation::Fold,
+            4 => Saturation::Diode,
+            _ => Saturation::Off,
+        }
+    }
 
-            ..Info::default()
+    fn to_param(self) -> f32 {
+        match self {
+            Saturation::Off => 0.0,
+            Saturation::Soft => 0.25,
+            Saturation::Hard => 0.5,
+            Saturation::Fold => 0.75,
+            Saturation::Diode => 1.0,
         }
     }
 
-    // sets parameters when host changes them.
-    fn set_parameter(&mut self, index: i32, value: f32) {
-        match index {
-            // delay time. delay_buffer is also reloaded. Because of this it's not possible to have a smooth change
-            // between one delay time and another. To prevent any issues when loading delay_buffer
-            // delay time cannot be zero.
-            0 => {
-                self.delay_time = value.max(0.001);
-                self.delay_buffer = reload_delay_buffer(self.sample_rate, self.delay_time);
+    fn shape(self, sample: f32) -> f32 {
+        match self {
+            Saturation::Off => sample,
+            Saturation::Soft => sample.tanh(),
+            Saturation::Hard => sample.clamp(-1.0, 1.0),
+            // a triangle wave, so it goes 0 to 1 and back down as the input goes 0 to 2 (and so on)
+            Saturation::Fold => 1.0 - ((sample + 1.0).rem_euclid(4.0) - 2.0).abs(),
+            Saturation::Diode => if sample >= 0.0 { sample.tanh() } else { (sample * 2.0).tanh() * 0.5 },
+        }
+    }
+}
+
+// the choices for the Mono Output parameter
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MonoOutput {
+    Off,
+    // left and right both get (left + right) / 2
+    Mono,
+    // the same, but with the top end rolled off at MONO_LOFI_CUTOFF too, like an old radio or record
+    LoFi,
+}
+
+impl MonoOutput {
+    // the host gives us 0 to 1, split into three equal steps
+    fn from_param(value: f32) -> MonoOutput {
+        match (value * 2.0).round() as i32 {
+            1 => MonoOutput::Mono,
+            2 => MonoOutput::LoFi,
+            _ => MonoOutput::Off,
+        }
+    }
+
+    fn to_param(self) -> f32 {
+        match self {
+            MonoOutput::Off => 0.0,
+            MonoOutput::Mono => 0.5,
+            MonoOutput::LoFi => 1.0,
+        }
+    }
+}
+
+// the choices for the Output Routing parameter. The split ones put the dry on one side and the wet on the other,
+// like a hardware delay with separate dry and wet outs, so they can be sent different places after the plugin.
+// Each side gets both of its channels summed to mono, at the level Dry/Wet gives it (Dry Pan, Invert Dry and
+// Mix Lock all still apply). Stutter, the looper, Mono Output and so on come after, so they still see both
+// sides. Feedback Tap's Post-Mix takes the normal stereo mix either way
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputRouting {
+    // dry and wet mixed together on both sides, as usual
+    Stereo,
+    DryLeftWetRight,
+    WetLeftDryRight,
+}
+
+impl OutputRouting {
+    // the host gives us 0 to 1, split into three equal steps
+    fn from_param(value: f32) -> OutputRouting {
+        match (value * 2.0).round() as i32 {
+            1 => OutputRouting::DryLeftWetRight,
+            2 => OutputRouting::WetLeftDryRight,
+            _ => OutputRouting::Stereo,
+        }
+    }
+
+    fn to_param(self) -> f32 {
+        match self {
+            OutputRouting::Stereo => 0.0,
+            OutputRouting::DryLeftWetRight => 0.5,
+            OutputRouting::WetLeftDryRight => 1.0,
+        }
+    }
+}
+
+// the choices for the Channel Mode parameter, which picks how the two sides of the delay relate to each other
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChannelMode {
+    // two delays with the same settings, one per side. What goes in on the left only ever comes out on the left.
+    // This is how it's always worked
+    DualMono,
+    // the same, but each time round the loop CROSS_LINK_AMOUNT of each side's feedback goes to the other side,
+    // so the echoes spread across the stereo field as they repeat
+    CrossLinked,
+    // the delays run on mid and side instead of left and right, the same as Stereo Mode's Mid/Side
+    MidSide,
+}
+
+impl ChannelMode {
+    // the host gives us 0 to 1, split into three equal steps
+    fn from_param(value: f32) -> ChannelMode {
+        match (value * 2.0).round() as i32 {
+            1 => ChannelMode::CrossLinked,
+            2 => ChannelMode::MidSide,
+            _ => ChannelMode::DualMono,
+        }
+    }
+
+    fn to_param(self) -> f32 {
+        match self {
+            ChannelMode::DualMono => 0.0,
+            ChannelMode::CrossLinked => 0.5,
+            ChannelMode::MidSide => 1.0,
+        }
+    }
+}
+
+// the choices for the Duck Source parameter, ie. what the ducking listens to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DuckSource {
+    // the input, after the trim and routing. That's the dry on its own, and the wet never gets into it,
+    // so the ducking can't chase its own tail. This is how it's always worked
+    Input,
+    // the output with the wet left out, ie. the dry as it's heard: after Dry Pan, Invert Dry and however far
+    // down Dry/Wet has it, one sample late like Output. Turning the dry down turns the ducking down with it,
+    // and there's still no wet for it to chase
+    Dry,
+    // the plugin's own output, dry and wet together, one sample late. The echoes duck themselves too, so it pumps
+    Output,
+    // the 3rd and 4th inputs, eg. a kick drum from another track. If the host hasn't given us those
+    // (or it's process_block) there's nothing to listen to, so it listens to the input instead
+    Sidechain,
+}
+
+impl DuckSource {
+    // The host gives us 0 to 1, split into four equal steps. Dry was added last but goes second,
+    // which keeps 0.5 (rounding up) as Output and 1 as Sidechain, like they were with three steps
+    fn from_param(value: f32) -> DuckSource {
+        match (value * 3.0).round() as i32 {
+            1 => DuckSource::Dry,
+            2 => DuckSource::Output,
+            3 => DuckSource::Sidechain,
+            _ => DuckSource::Input,
+        }
+    }
+
+    fn to_param(self) -> f32 {
+        match self {
+            DuckSource::Input => 0.0,
+            DuckSource::Dry => 1.0 / 3.0,
+            DuckSource::Output => 2.0 / 3.0,
+            DuckSource::Sidechain => 1.0,
+        }
+    }
+}
+
+// The choices for the Interpolation parameter, ie. how a read that falls between two samples is worked out.
+// It's how both delay lines are read (see DelayLine::read), so it matters for any delay that isn't a whole
+// number of samples, and for Motion, which is always moving between them.
+// None is the cheapest, cubic costs about three times what linear does
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    // whichever sample is nearest, so a delay between two samples is rounded to one of them. Costs nothing,
+    // but the steps add a little grit as the read moves
+    None,
+    // a straight line between the two samples either side. Dulls the very top slightly
+    Linear,
+    // a curve through the four nearest samples (Catmull-Rom), which keeps the top end
+    Cubic,
+}
+
+impl Interpolation {
+    // the host gives us 0 to 1, split into three equal steps
+    fn from_param(value: f32) -> Interpolation {
+        match (value * 2.0).round() as i32 {
+            1 => Interpolation::Linear,
+            2 => Interpolation::Cubic,
+            _ => Interpolation::None,
+        }
+    }
+
+    fn to_param(self) -> f32 {
+        match self {
+            Interpolation::None => 0.0,
+            Interpolation::Linear => 0.5,
+            Interpolation::Cubic => 1.0,
+        }
+    }
+
+    // somewhere between newer and older, fraction of the way to older. before is the sample before newer
+    // (ie. newer still) and after is the one after older
+    fn read(self, before: LoopSample, newer: LoopSample, older: LoopSample, after: LoopSample, fraction: LoopSample) -> LoopSample {
+        match self {
+            Interpolation::None => if fraction < 0.5 { newer } else { older },
+            Interpolation::Linear => newer + (older - newer) * fraction,
+            Interpolation::Cubic => {
+                let slope_newer = (older - before) * 0.5;
+                let slope_older = (after - newer) * 0.5;
+                let a = 2.0 * (newer - older) + slope_newer + slope_older;
+                let b = 3.0 * (older - newer) - 2.0 * slope_newer - slope_older;
+                ((a * fraction + b) * fraction + slope_newer) * fraction + newer
             },
-            // I don't want any problems below FEEDBACK_FACTOR value, so minimum cap of feedback is 10%
-            // although in reality that is equivalent to 0 feedback.
-            1 => self.feedback_amt = value.max(0.1),
-            2 => self.dry_wet = value,
-            _ => (),
         }
     }
+}
 
-    // provides params when host asks for them.
-    fn get_parameter(&self, index: i32) -> f32 {
-       match index {
-           0 => self.delay_time,
-           1 => self.feedback_amt,
-           2 => self.dry_wet,
-           _ => 0.0,
-       }
+// The MIDI notes being held down, oldest first, for Note Delay. A fixed array so a note never allocates
+#[derive(Clone, Copy, Default)]
+struct HeldNotes {
+    notes: [u8; MAX_HELD_NOTES],
+    count: usize,
+}
+
+impl HeldNotes {
+    // the newest note goes on the end. If it was already held (a missed note off) it moves there
+    fn press(&mut self, note: u8) {
+        self.release(note);
+        if self.count == MAX_HELD_NOTES {
+            self.notes.copy_within(1.., 0);
+            self.count -= 1;
+        }
+        self.notes[self.count] = note;
+        self.count += 1;
     }
 
-    // provides param names when host asks for them.
-    fn get_parameter_name(&self, index: i32) -> String {
-        match index {
-            0 => "Delay Time".to_string(),
-            1 => "Feedback".to_string(),
-            2 => "Dry/Wet".to_string(),
-            _ => "".to_string(),
+    fn release(&mut self, note: u8) {
+        if let Some(index) = self.notes[..self.count].iter().position(|&held| held == note) {
+            self.notes.copy_within(index + 1..self.count, index);
+            self.count -= 1;
         }
     }
 
-    // param value text.
-    fn get_parameter_text(&self, index: i32) -> String {
-        match index {
-            // all params go from 0 to 1. Delay time is multiplied by two later
-            // because I wanted a longer delay time. 
-            0 => format!("{}", self.delay_time * 2000.0),
-            1 => format!("{}", self.feedback_amt * 100.0),
-            2 => format!("{}", self.dry_wet * 100.0),
-            _ => "".to_string(),
+    // the most recent note still held, if any
+    fn last(&self) -> Option<u8> {
+        self.count.checked_sub(1).map(|index| self.notes[index])
+    }
+}
+
+// the two slots Morph goes between, see store_snapshot
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Snapshot {
+    A,
+    B,
+}
+
+// the choices for the Test Signal parameter
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TestSignal {
+    Off,
+    // a steady sine, to hear what the delay does to a tone
+    Tone,
+    // a single-sample click every so often, to hear the echoes on their own
+    Impulse,
+}
+
+impl TestSignal {
+    // the host gives us 0 to 1, split into three equal steps
+    fn from_param(value: f32) -> TestSignal {
+        match (value * 2.0).round() as i32 {
+            1 => TestSignal::Tone,
+            2 => TestSignal::Impulse,
+            _ => TestSignal::Off,
         }
     }
 
-    // param labels. 
-    fn get_parameter_label(&self, index: i32) -> String {
-        match index {
-            0 => "ms".to_string(),
-            1 => "%".to_string(),
-            2 => "%".to_string(),
-            _ => "".to_string(),
+    fn to_param(self) -> f32 {
+        match self {
+            TestSignal::Off => 0.0,
+            TestSignal::Tone => 0.5,
+            TestSignal::Impulse => 1.0,
         }
     }
+}
 
-    // in the case that the host changes sample rate
-    // this function is called. We update the sample_rate held in SillyDelay
-    // and also reload the delay_buffer to reflect the new sample_rate
-    fn set_sample_rate(&mut self, sample_rate: f32) {
+// what can go wrong reading settings back in with import_settings
+#[derive(Clone, Debug, PartialEq)]
+pub enum SettingsError {
+    // the line (counting from 1) isn't a `"Name" = value` pair
+    BadLine(usize),
+    // nothing is called that
+    UnknownParameter(String),
+    // the value isn't a number between 0 and 1
+    BadValue(String),
+}
+
+/// For using SillyDelay straight from Rust without a VST host.
+/// Anything not set keeps the same value a freshly loaded plugin would have.
+///
+/// ```
+/// use ::SillyDelay::{ConfigError, SillyDelay};
+///
+/// let delay = SillyDelay::builder().sample_rate(48000.0).delay_ms(250.0).feedback(0.4).dry_wet(0.5).build();
+/// assert!(delay.is_ok());
+/// // anything out of range comes back as an error instead
+/// let delay = SillyDelay::builder().delay_ms(5000.0).build();
+/// assert_eq!(delay.err(), Some(ConfigError::DelayTime(5000.0)));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct SillyDelayBuilder {
+    sample_rate: f32,
+    delay_ms: f32,
+    feedback: f32,
+    dry_wet: f32,
+}
+
+// what build() complains about, with the value that was out of range
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConfigError {
+    // has to be above 0
+    SampleRate(f32),
+    // has to be between 0.1 and 2000ms
+    DelayTime(f32),
+    // has to be between 0 and 1
+    Feedback(f32),
+    // has to be between 0 and 1
+    DryWet(f32),
+}
+
+// Things worth knowing about when someone reports a weird noise. Only recorded with the "diagnostics"
+// feature, see SillyDelay::diagnostics.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Diagnostic {
+    // a block bigger than the host said it would send turned up, so process had to allocate
+    ScratchGrown(usize),
+    // the host sent a sample rate we can't use, so it was ignored
+    BadSampleRate(f32),
+    // the delay lines were thrown away and made again (sample rate change, new delay time or Clear)
+    BuffersRebuilt,
+}
+
+impl SillyDelayBuilder {
+    pub fn sample_rate(mut self, sample_rate: f32) -> SillyDelayBuilder {
         self.sample_rate = sample_rate;
-        self.delay_buffer = reload_delay_buffer(sample_rate, self.delay_time);
-        
+        self
     }
 
-    // main processing goes here
-    fn process(&mut self, buffer: &mut AudioBuffer<f32>) {
-        // stores feedback values for later
-        // needs to be mutable and set to 0 or it won't work
-        let (mut fb_l, mut fb_r) = (0f32, 0f32);
-
-        // split the audio buffer, and split the inputs buffer
-        // into left and right channels with split_at() 
-        // outputs has to be mutable borrow 
-        let (inputs, mut outputs) = buffer.split();
-        let (in_l, in_r) = inputs.split_at(1);
-    
-        // split the outputs buffer into left and right channels, both mutable
-        let (out_l, out_r) = outputs.split_at_mut(1);
-
-        // ok this is weird. There is definitely a better way of doing this.
-        // in order to get to the samples, channels need to be further destructured
-        // since there's 4 things to iterate/zip over it would have been an issue to nest 
-        // a for loop within another. 
-        // each zip adds to a tuple, going outwards, hence the weird (((x,x),x)x) thing.
-        // since we have "Inputs" or "Outputs" types, into_iter is necessary 
-        // to get InputIterators and OutputIterators 
-
-        // sidenote: l / r is left, right; b is buffer, s is sample.
-        for (((in_l_b, in_r_b), out_l_b), out_r_b) in in_l
-        .into_iter()
-        .zip(in_r.into_iter())
-        .zip(out_l.into_iter())
-        .zip(out_r.into_iter())
-        {
-            // repeat the process to get the values from the slices
-            for (((in_l_s, in_r_s), out_l_s), out_r_s) in in_l_b
-            .iter()
-            .zip(in_r_b)
-            .zip(out_l_b)
-            .zip(out_r_b)
-            {
-                // delay_buffer is a CircularBuffer 
-                // it has a maximum size, and each time something is added, it will pop the next thing in queue
-                // First In First Out. Because delay_buffer is immediately filled in with 0s there's no case where
-                // adding something will return None
-                if let Some((temp_l, temp_r)) = self.delay_buffer
-                // dereference the inputs (in_l_s, in_r_s) to get the values, and add the feedback 
-                .add((*in_l_s+fb_l, *in_r_s+fb_r))
-                // convert the Result into an Option and discard error and then get the tuple value returned to (temp_l, temp_r)
-                .ok().unwrap() {
-                    // if successful (ie, there is Some(value))
-                    // add popped values from delay_buffer into feedback variables
-                    // feedback_amt - FEEDBACK_FACTOR always ensures the value is between 
-                    // 0 and 0.9 - to prevent, well, too much feedback
-                    fb_l = temp_l * (self.feedback_amt - FEEDBACK_FACTOR);
-                    fb_r = temp_r * (self.feedback_amt - FEEDBACK_FACTOR);
-
-                    // replace the output samples with a mix of the popped values from the delay_buffer
-                    // and the original value, depending on dry/wet percentage
-                    // Possible expansion: Allow possibility to have unsynced left and right delays
-                    *out_l_s = mix_samples(*out_l_s, temp_l, self.dry_wet);
-                    *out_r_s = mix_samples(*out_r_s, temp_r, self.dry_wet);
-                }
+    pub fn delay_ms(mut self, delay_ms: f32) -> SillyDelayBuilder {
+        self.delay_ms = delay_ms;
+        self
+    }
 
-            }
+    // same as the Feedback parameter, so anything up to 0.1 means no feedback
+    pub fn feedback(mut self, feedback: f32) -> SillyDelayBuilder {
+        self.feedback = feedback;
+        self
+    }
+
+    pub fn dry_wet(mut self, dry_wet: f32) -> SillyDelayBuilder {
+        self.dry_wet = dry_wet;
+        self
+    }
+
+    // checks everything is in range, then sets it all up the same way a host would
+    pub fn build(self) -> Result<SillyDelay, ConfigError> {
+        // NaN isn't contained in any range, so it fails these too
+        if !self.sample_rate.is_finite() || self.sample_rate <= 0.0 {
+            return Err(ConfigError::SampleRate(self.sample_rate));
         }
-     }
+        if !(0.1..=2000.0).contains(&self.delay_ms) {
+            return Err(ConfigError::DelayTime(self.delay_ms));
+        }
+        if !(0.0..=1.0).contains(&self.feedback) {
+            return Err(ConfigError::Feedback(self.feedback));
+        }
+        if !(0.0..=1.0).contains(&self.dry_wet) {
+            return Err(ConfigError::DryWet(self.dry_wet));
+        }
+
+        // the sample rate goes last: changing the delay time leaves a Clear fading out,
+        // and set_sample_rate builds the buffers fresh and cancels it
+        let mut delay = SillyDelay::default();
+        delay.set_parameter(Param::DelayTime.to_i32(), delay_to_knob(self.delay_ms / 2000.0));
+        delay.set_parameter(Param::Feedback.to_i32(), self.feedback);
+        delay.set_parameter(Param::DryWet.to_i32(), self.dry_wet);
+        delay.set_sample_rate(self.sample_rate);
+        Ok(delay)
+    }
 }
 
-fn mix_samples(original: f32, added: f32, amount: f32) -> f32 {
-    // always ensures that there's never more than 100%
-    // if dry_wet (amount) is 60%, dry amount is 0.4, wet is 0.6
-    let dry = 1.0 - amount;
-    // return the mixed value
-    (original*dry) + (added*amount)
+// a peak meter with peak-hold and release. Left and right share a meter, whichever is louder wins.
+#[derive(Clone, Copy, Default)]
+struct PeakMeter {
+    peak: f32,
+    // samples left before the peak starts falling
+    hold: usize,
+    // what the peak is multiplied by every sample once hold runs out
+    release: f32,
+    hold_samples: usize,
+    // samples left before the clip light goes out, 0 means it's off
+    clip: usize,
+    clip_samples: usize,
 }
 
-fn reload_delay_buffer(sample_rate: f32, delay_time: f32) -> CircularBuffer<(f32, f32)> {
-    // by having this in one place, it reduces the amount of places where CircularBuffer is called
-    // and it doesn't need to have access to delay_time or sample_rate directly from SillyDelay
-    // in case, for example, they're not initialized yet
-    // A problem with this is that any time delay time is changed the whole buffer is cleaned out.
+impl PeakMeter {
+    fn new(sample_rate: f32) -> PeakMeter {
+        PeakMeter {
+            peak: 0.0,
+            hold: 0,
+            // falls by 60dB (0.001) over METER_RELEASE_TIME
+            release: 0.001f32.powf(1.0 / (sample_rate * METER_RELEASE_TIME).max(1.0)),
+            hold_samples: (sample_rate * METER_HOLD_TIME) as usize,
+            clip: 0,
+            clip_samples: (sample_rate * CLIP_HOLD_TIME) as usize,
+        }
+    }
 
-    // A sample rate is always in (kilo)Hertz, ie. per Second. Pretty obvious, but I forgot for a moment earlier.
-    // So to ensure a maximum of 2 seconds - the size of our delay_buffer has to be the sample rate times 2.
-    // (delay_time can only go up to 1.0 maximum)
-    // if the delay time chosen is less than that, for example, 200ms, we need to use a smaller delay_buffer
-    // hence rate * time * 2
-    let size = (sample_rate * delay_time * 2.) as usize;
-    // buffer is immediately populated with tuples of 0,0 so that each .add pops Some(value)
-    // tuple because (left, right)
-    CircularBuffer::with_default(size, (0f32, 0f32))
+    fn update(&mut self, left: f32, right: f32) {
+        let level = left.abs().max(right.abs());
+        // this only looks at the samples themselves, so a peak between two samples won't light it
+        if level > 1.0 {
+            self.clip = self.clip_samples;
+        } else if self.clip > 0 {
+            self.clip -= 1;
+        }
+        if level >= self.peak {
+            self.peak = level;
+            self.hold = self.hold_samples;
+        } else if self.hold > 0 {
+            self.hold -= 1;
+        } else {
+            self.peak *= self.release;
+        }
+    }
 }
 
-// necessary to compile to VST
-plugin_main!(SillyDelay);
\ No newline at end of file
+// The last DIAGNOSTICS_KEPT diagnostics, oldest gets overwritten first.
+// A fixed array so recording one from the audio thread never allocates.
+#[cfg(feature = "diagnostics")]
+#[derive(Default)]
+struct Diagnostics {
+    events: [Option<Diagnostic>; DIAGNOSTICS_KEPT],
+    // where the next one goes
+    next: usize,
+}
+
+#[cfg(feature = "diagnostics")]
+impl Diagnostics {
+    fn push(&mut self, event: Diagnostic) {
+        self.events[self.next] = Some(event);
+        self.next = (self.next + 1) % DIAGNOSTICS_KEPT;
+    }
+
+    // oldest first
+    fn iter(&self) -> impl Iterator<Item = Diagnostic> + '_ {
+        self.events[self.next..].iter().chain(&self.events[..self.next]).flatten().copied()
+    }
+}
+
+// How long blocks take to process, as a percentage of how long they last: 100% is just keeping up with
+// real time, past that the host will be dropping out. Only built with the "cpu-usage" feature, see
+// SillyDelay::cpu_usage. It times from the start of process_scratch to the end, so it leaves out the host's
+// side of things and copying the block in, and on a busy machine it counts time spent waiting for other threads too
+#[cfg(feature = "cpu-usage")]
+#[derive(Default)]
+struct CpuMeter {
+    usage: f32,
+}
+
+#[cfg(feature = "cpu-usage")]
+impl CpuMeter {
+    // Smoothed over CPU_USAGE_SMOOTH_TIME of audio, not a number of blocks, so it settles just as fast
+    // whatever the block size
+    fn update(&mut self, elapsed: std::time::Duration, samples: usize, sample_rate: f32) {
+        if samples == 0 {
+            return;
+        }
+        let block_time = samples as f32 / sample_rate;
+        let usage = elapsed.as_secs_f32() / block_time * 100.0;
+        let coeff = -(-block_time / CPU_USAGE_SMOOTH_TIME).exp_m1();
+        self.usage += (usage - self.usage) * coeff;
+    }
+}
+
+// The "smear" fades echoes in instead of letting them start instantly.
+// Because there's only one buffer that everything recirculates through, there's no way of knowing
+// where one repeat ends and the next begins. So instead it watches the signal coming out of the delay
+// and whenever it jumps up suddenly (an input transient coming back around, i.e. a new echo) the wet
+// gain drops to 0 and ramps back up over the attack time.
+// This works well on drums, plucks and other spiky material. It does little on pads or anything without
+// clear attacks, and if echoes overlap (short delay times, lots of feedback) a new echo restarts the ramp
+// and cuts off the previous one's swell. It only touches the wet output, not what goes back into feedback.
+struct Smear {
+    fast: f32,
+    slow: f32,
+    fast_coeff: f32,
+    slow_coeff: f32,
+    // current wet gain, and how much it goes up by each sample
+    gain: f32,
+    step: f32,
+    // stops one transient from retriggering the ramp over and over while it's still above threshold
+    triggered: bool,
+}
+
+impl Smear {
+    fn new(sample_rate: f32, attack: f32) -> Smear {
+        let mut smear = Smear {
+            fast: 0.0,
+            slow: 0.0,
+            fast_coeff: smoothing_coeff(SMEAR_FAST_TIME, sample_rate),
+            slow_coeff: smoothing_coeff(SMEAR_SLOW_TIME, sample_rate),
+            gain: 1.0,
+            step: 1.0,
+            triggered: false,
+        };
+        smear.set_attack(sample_rate, attack);
+        smear
+    }
+
+    // Only changes how fast the gain ramps back up. The followers carry on where they were, since starting
+    // them again from 0 makes whatever's playing look like a new transient and drops the wet out
+    fn set_attack(&mut self, sample_rate: f32, attack: f32) {
+        let attack_samples = attack * MAX_ATTACK_TIME * sample_rate;
+        // an attack shorter than a sample means gain goes straight back to 1, ie. no smear
+        self.step = 1.0 / attack_samples.max(1.0);
+    }
+
+    // takes the wet samples, returns the gain to apply to them
+    fn process(&mut self, left: f32, right: f32) -> f32 {
+        let level = left.abs().max(right.abs());
+        self.fast += (level - self.fast) * self.fast_coeff;
+        self.slow += (level - self.slow) * self.slow_coeff;
+
+        let transient = self.fast > self.slow * SMEAR_THRESHOLD;
+        if transient && !self.triggered {
+            self.gain = 0.0;
+        }
+        self.triggered = transient;
+
+        self.gain = (self.gain + self.step).min(1.0);
+        self.gain
+    }
+}
+
+// A Schroeder allpass: a short delay with feedforward and feedback that leaves the level of every frequency
+// alone and only moves phase around. With a different length on each side a mono wet comes out
+// as two different signals, which sounds wider. Summed back to mono the phase differences do cause
+// some comb filtering, but much less than just delaying one side would.
+struct Allpass {
+    buffer: Vec<f32>,
+    pos: usize,
+}
+
+impl Allpass {
+    fn new(sample_rate: f32, time: f32) -> Allpass {
+        Allpass {
+            buffer: vec![0.0; next_prime((sample_rate * time) as usize)],
+            pos: 0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.buffer[self.pos];
+        let fed_back = input + delayed * DECORRELATE_GAIN;
+        self.buffer[self.pos] = fed_back;
+        self.pos = (self.pos + 1) % self.buffer.len();
+        delayed - fed_back * DECORRELATE_GAIN
+    }
+}
+
+// Beat-repeat. Keeps a record of the last MAX_STUTTER_TIME of input, and when triggered copies the most
+// recent slice out of it and plays that over and over in place of the normal output. The delay keeps
+// running underneath the whole time, so it picks up where it was when the stutter ends.
+struct Stutter {
+    // the recent input, written round and round
+    history: Vec<(f32, f32)>,
+    history_pos: usize,
+    // the slice being repeated, and how much of it is in use
+    slice: Vec<(f32, f32)>,
+    slice_len: usize,
+    // where we are in the slice, and how many more times it plays (0 means the stutter is off)
+    pos: usize,
+    repeats_left: usize,
+    // set when triggered, the capture happens in process so it gets the newest input
+    pending: Option<(usize, usize)>,
+    // 0 to 1, how much of the output is stutter. Ramps so going in and out doesn't click
+    mix: f32,
+    fade_samples: usize,
+}
+
+impl Stutter {
+    fn new(sample_rate: f32) -> Stutter {
+        let size = ((sample_rate * MAX_STUTTER_TIME) as usize).max(1);
+        Stutter {
+            history: vec![(0.0, 0.0); size],
+            history_pos: 0,
+            slice: vec![(0.0, 0.0); size],
+            slice_len: 0,
+            pos: 0,
+            repeats_left: 0,
+            pending: None,
+            mix: 0.0,
+            fade_samples: ((sample_rate * STUTTER_FADE_TIME) as usize).max(1),
+        }
+    }
+
+    fn trigger(&mut self, slice_len: usize, repeats: usize) {
+        self.pending = Some((slice_len.clamp(1, self.history.len()), repeats));
+    }
+
+    // takes the input (to remember) and the normal output, returns the output with the stutter over it
+    fn process(&mut self, input: (f32, f32), output: (f32, f32)) -> (f32, f32) {
+        self.history[self.history_pos] = input;
+        self.history_pos = (self.history_pos + 1) % self.history.len();
+
+        if let Some((slice_len, repeats)) = self.pending.take() {
+            // the slice is the last slice_len samples, oldest first
+            let start = (self.history_pos + self.history.len() - slice_len) % self.history.len();
+            for i in 0..slice_len {
+                self.slice[i] = self.history[(start + i) % self.history.len()];
+            }
+            self.slice_len = slice_len;
+            self.pos = 0;
+            self.repeats_left = repeats;
+        }
+
+        // ramp towards fully stutter while it's running, and back to the normal output when it's done
+        let step = 1.0 / self.fade_samples as f32;
+        self.mix = if self.repeats_left > 0 { (self.mix + step).min(1.0) } else { (self.mix - step).max(0.0) };
+        if self.mix == 0.0 {
+            return output;
+        }
+
+        // fade each slice in and out so the loop point doesn't click
+        let fade = self.fade_samples.min(self.slice_len / 2).max(1) as f32;
+        let window = (self.pos as f32 / fade)
+            .min((self.slice_len - self.pos) as f32 / fade)
+            .min(1.0);
+        let (slice_l, slice_r) = self.slice[self.pos];
+
+        if self.repeats_left > 0 {
+            self.pos += 1;
+            if self.pos == self.slice_len {
+                self.pos = 0;
+                self.repeats_left -= 1;
+            }
+        }
+
+        (
+            output.0 + (slice_l * window - output.0) * self.mix,
+            output.1 + (slice_r * window - output.1) * self.mix,
+        )
+    }
+}
+
+// A looper, separate from the delay. When triggered it records the next loop length of input into its own buffer,
+// then plays it round and round on top of the output. With overdub on, the input is added into the loop as it plays,
+// so it builds up in layers. The recording runs on LOOP_FADE_TIME past the end of the loop, and that extra bit is
+// crossfaded into the start, so going round from the end to the start sounds like the recording just carried on
+// rather than clicking. Triggering it again records a new loop over the old one, which fades out as it starts.
+struct Looper {
+    buffer: Vec<(f32, f32)>,
+    // how long the loop that's playing is, 0 until something has been recorded
+    len: usize,
+    // where the next sample is recorded, and where it plays from
+    write: usize,
+    read: usize,
+    // the length of the loop being recorded, if there is one
+    recording: Option<usize>,
+    // set when triggered with the length to record, it starts in process like the stutter's capture
+    pending: Option<usize>,
+    // 0 to 1, how much of the loop is heard. Ramps so it doesn't click in or out
+    mix: f32,
+    fade_samples: usize,
+}
+
+impl Looper {
+    fn new(sample_rate: f32) -> Looper {
+        let fade_samples = ((sample_rate * LOOP_FADE_TIME) as usize).max(1);
+        Looper {
+            buffer: vec![(0.0, 0.0); ((sample_rate * MAX_LOOP_TIME) as usize).max(1) + fade_samples],
+            len: 0,
+            write: 0,
+            read: 0,
+            recording: None,
+            pending: None,
+            mix: 0.0,
+            fade_samples,
+        }
+    }
+
+    fn trigger(&mut self, len: usize) {
+        self.pending = Some(len.clamp(self.fade_samples, self.buffer.len() - self.fade_samples));
+    }
+
+    // takes the input (to record) and the output, returns the output with the loop added
+    fn process(&mut self, input: (f32, f32), output: (f32, f32), overdub: bool, level: f32) -> (f32, f32) {
+        if let Some(len) = self.pending.take() {
+            self.recording = Some(len);
+            self.write = 0;
+        }
+
+        if let Some(len) = self.recording {
+            self.buffer[self.write] = input;
+            self.write += 1;
+            if self.write == len + self.fade_samples {
+                // the start fades from the run-on (which carries on from the end) to what was really recorded there
+                for i in 0..self.fade_samples {
+                    let amount = i as f32 / self.fade_samples as f32;
+                    let (start, run_on) = (self.buffer[i], self.buffer[len + i]);
+                    self.buffer[i] = (
+                        run_on.0 + (start.0 - run_on.0) * amount,
+                        run_on.1 + (start.1 - run_on.1) * amount,
+                    );
+                }
+                self.recording = None;
+                self.len = len;
+                self.read = 0;
+            }
+        }
+
+        // playing once there's a loop and nothing new is being recorded, otherwise fading out
+        let step = 1.0 / self.fade_samples as f32;
+        let playing = self.len > 0 && self.recording.is_none();
+        self.mix = if playing { (self.mix + step).min(1.0) } else { (self.mix - step).max(0.0) };
+        if self.mix == 0.0 {
+            return output;
+        }
+
+        // While it fades out for a new recording it carries on round the old loop, even though the start of it
+        // is being recorded over. It's only for LOOP_FADE_TIME, and it's on its way out anyway
+        let (loop_l, loop_r) = self.buffer[self.read];
+        // added after it's been read, so it's heard next time round
+        if overdub && playing {
+            self.buffer[self.read] = (loop_l + input.0, loop_r + input.1);
+        }
+        self.read = (self.read + 1) % self.len;
+
+        let gain = self.mix * level;
+        (output.0 + loop_l * gain, output.1 + loop_r * gain)
+    }
+}
+
+// Follows the level of the input so the wet can be turned down while someone's playing and come back
+// up in the gaps. Fast attack, slow release.
+struct Ducker {
+    env: f32,
+    attack_coeff: f32,
+    release_coeff: f32,
+}
+
+impl Ducker {
+    fn new(sample_rate: f32) -> Ducker {
+        Ducker {
+            env: 0.0,
+            attack_coeff: smoothing_coeff(DUCK_ATTACK_TIME, sample_rate),
+            release_coeff: smoothing_coeff(DUCK_RELEASE_TIME, sample_rate),
+        }
+    }
+
+    // takes the input, returns the envelope (capped at 1.0, full scale)
+    fn process(&mut self, left: f32, right: f32) -> f32 {
+        let level = left.abs().max(right.abs()).min(1.0);
+        let coeff = if level > self.env { self.attack_coeff } else { self.release_coeff };
+        self.env += (level - self.env) * coeff;
+        self.env
+    }
+}
+
+// Measures the correlation of the wet and, with Mono Safe on, narrows it if that drops below the floor.
+// It listens to what it puts out, so it narrows just far enough to get back above the floor and no further.
+// Width only ever moves a little each sample (full to mono over MONO_SAFE_TIME), so it never jumps.
+// When the wet comes back above the floor, or Mono Safe is off, it goes back to full width the same way.
+struct Width {
+    // running averages of l*r, l*l and r*r
+    lr: f32,
+    ll: f32,
+    rr: f32,
+    coeff: f32,
+    // 1 is untouched, 0 is mono
+    width: f32,
+    step: f32,
+}
+
+impl Width {
+    fn new(sample_rate: f32) -> Width {
+        Width {
+            lr: 0.0,
+            ll: 0.0,
+            rr: 0.0,
+            coeff: smoothing_coeff(CORRELATION_TIME, sample_rate),
+            width: 1.0,
+            step: 1.0 / (MONO_SAFE_TIME * sample_rate).max(1.0),
+        }
+    }
+
+    // silence counts as fully correlated, there's nothing there to cancel
+    fn correlation(&self) -> f32 {
+        let power = (self.ll * self.rr).sqrt();
+        if power > 1e-9 { (self.lr / power).clamp(-1.0, 1.0) } else { 1.0 }
+    }
+
+    // floor is None when Mono Safe is off
+    fn process(&mut self, left: f32, right: f32, floor: Option<f32>) -> (f32, f32) {
+        // at full width it's left alone. Going through mid/side and back isn't quite exact in floating point,
+        // so a tiny bit of each side would end up in the other
+        let (left, right) = if self.width < 1.0 {
+            let (mid, side) = to_mid_side(left, right);
+            from_mid_side(mid, side * self.width)
+        } else {
+            (left, right)
+        };
+
+        self.lr += (left * right - self.lr) * self.coeff;
+        self.ll += (left * left - self.ll) * self.coeff;
+        self.rr += (right * right - self.rr) * self.coeff;
+
+        self.width = match floor {
+            Some(floor) if self.correlation() < floor => (self.width - self.step).max(0.0),
+            _ => (self.width + self.step).min(1.0),
+        };
+        (left, right)
+    }
+}
+
+// A standard 2nd order filter (RBJ cookbook biquad), low-pass, high-pass or all-pass
+#[derive(Clone, Copy, Default)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    // previous inputs and outputs
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl Biquad {
+    // a fresh low-pass, with nothing in its memory
+    fn low_pass(sample_rate: f32, cutoff: f32, q: f32) -> Biquad {
+        let mut filter = Biquad::default();
+        filter.set_low_pass(sample_rate, cutoff, q);
+        filter
+    }
+
+    // cos(w0) and alpha from the cookbook. Worked out in f64: at 192kHz cos(w0) for 20Hz is within 0.0000003 of 1,
+    // so in f32 most of 1 - cos(w0) is lost, and a low-pass down there (that's all its gain) comes out about 10% off
+    fn cookbook(sample_rate: f32, freq: f32, q: f32) -> (f64, f64) {
+        let w0 = 2.0 * std::f64::consts::PI * freq as f64 / sample_rate as f64;
+        (w0.cos(), w0.sin() / (2.0 * q as f64))
+    }
+
+    // normalises by a0 (still in f64) before they're stored
+    fn set_coefficients(&mut self, b0: f64, b1: f64, b2: f64, a0: f64, a1: f64, a2: f64) {
+        self.b0 = (b0 / a0) as f32;
+        self.b1 = (b1 / a0) as f32;
+        self.b2 = (b2 / a0) as f32;
+        self.a1 = (a1 / a0) as f32;
+        self.a2 = (a2 / a0) as f32;
+    }
+
+    // changes the coefficients but keeps the filter's memory, so it can be retuned while running
+    fn set_low_pass(&mut self, sample_rate: f32, cutoff: f32, q: f32) {
+        let (cos_w0, alpha) = Biquad::cookbook(sample_rate, cutoff, q);
+        self.set_coefficients(
+            (1.0 - cos_w0) / 2.0, 1.0 - cos_w0, (1.0 - cos_w0) / 2.0,
+            1.0 + alpha, -2.0 * cos_w0, 1.0 - alpha,
+        );
+    }
+
+    // a fresh high-pass, with nothing in its memory
+    fn high_pass(sample_rate: f32, cutoff: f32, q: f32) -> Biquad {
+        let mut filter = Biquad::default();
+        filter.set_high_pass(sample_rate, cutoff, q);
+        filter
+    }
+
+    // the same as set_low_pass, but a high-pass
+    fn set_high_pass(&mut self, sample_rate: f32, cutoff: f32, q: f32) {
+        let (cos_w0, alpha) = Biquad::cookbook(sample_rate, cutoff, q);
+        self.set_coefficients(
+            (1.0 + cos_w0) / 2.0, -(1.0 + cos_w0), (1.0 + cos_w0) / 2.0,
+            1.0 + alpha, -2.0 * cos_w0, 1.0 - alpha,
+        );
+    }
+
+    // same again for an all-pass: every frequency comes out at the same level, only the phase changes.
+    // The phase turns through 180 degrees at the centre frequency (360 by the top)
+    fn set_all_pass(&mut self, sample_rate: f32, centre: f32, q: f32) {
+        let (cos_w0, alpha) = Biquad::cookbook(sample_rate, centre, q);
+        self.set_coefficients(
+            1.0 - alpha, -2.0 * cos_w0, 1.0 + alpha,
+            1.0 + alpha, -2.0 * cos_w0, 1.0 - alpha,
+        );
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let output = self.b0 * input + self.b1 * self.x1 + self.b2 * self.x2
+            - self.a1 * self.y1 - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = input;
+        self.y2 = self.y1;
+        self.y1 = output;
+        output
+    }
+}
+
+// A rough bucket-brigade delay: everything coming out of delay_buffer goes through a steep (4th order)
+// low-pass that gets darker as the delay gets longer (see BBD_STAGES), picks up a little clock noise,
+// which also gets louder with longer delays, and the feedback gets gently squashed like a BBD's compander does.
+// Since the feedback is taken after all of this, every repeat is darker and a bit more squashed than the last.
+struct Bbd {
+    // two biquads in a row per channel, with the Q values for a 4th order Butterworth
+    filters_l: [Biquad; 2],
+    filters_r: [Biquad; 2],
+    noise_level: f32,
+    // for a cheap random number generator (xorshift)
+    noise_state: u32,
+}
+
+impl Bbd {
+    fn new(sample_rate: f32, delay_time: f32) -> Bbd {
+        let mut bbd = Bbd {
+            filters_l: [Biquad::default(); 2],
+            filters_r: [Biquad::default(); 2],
+            noise_level: 0.0,
+            noise_state: 0x1234_5678,
+        };
+        bbd.set_delay_time(sample_rate, delay_time);
+        bbd
+    }
+
+    // delay_time is 0 to 1 like the parameter, ie. up to 2 seconds
+    fn set_delay_time(&mut self, sample_rate: f32, delay_time: f32) {
+        let seconds = delay_time * 2.0;
+        // the top limit wins if the sample rate is so low the two overlap
+        let highest = sample_rate * 0.45;
+        let cutoff = (BBD_STAGES / (4.0 * seconds)).max(BBD_MIN_CUTOFF).min(highest);
+        for (filter, q) in self.filters_l.iter_mut().zip(&[0.541, 1.307]) {
+            filter.set_low_pass(sample_rate, cutoff, *q);
+        }
+        for (filter, q) in self.filters_r.iter_mut().zip(&[0.541, 1.307]) {
+            filter.set_low_pass(sample_rate, cutoff, *q);
+        }
+        self.noise_level = BBD_NOISE_LEVEL * (seconds / 2.0).sqrt();
+    }
+
+    fn noise(&mut self) -> f32 {
+        self.noise_state ^= self.noise_state << 13;
+        self.noise_state ^= self.noise_state >> 17;
+        self.noise_state ^= self.noise_state << 5;
+        // from 0 to u32::MAX to -1 to 1
+        (self.noise_state as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+
+    fn process(&mut self, left: f32, right: f32) -> (f32, f32) {
+        let left = self.filters_l.iter_mut().fold(left, |sample, filter| filter.process(sample));
+        let right = self.filters_r.iter_mut().fold(right, |sample, filter| filter.process(sample));
+        (left + self.noise() * self.noise_level, right + self.noise() * self.noise_level)
+    }
+}
+
+// Lo-fi: fewer bits (rounding every sample to a coarser step) and a lower sample rate (holding each
+// sample for a few samples instead of updating it every time). Both make a grittier, more aliased sound.
+struct Crusher {
+    held: (f32, f32),
+    // samples until the next one gets through
+    countdown: usize,
+}
+
+impl Crusher {
+    fn new() -> Crusher {
+        Crusher {
+            held: (0.0, 0.0),
+            countdown: 0,
+        }
+    }
+
+    // bits of 0 or less means don't quantize, factor 1 means don't downsample
+    fn process(&mut self, left: f32, right: f32, bits: f32, factor: usize) -> (f32, f32) {
+        if self.countdown == 0 {
+            self.held = (left, right);
+            self.countdown = factor;
+        }
+        self.countdown -= 1;
+
+        if bits <= 0.0 {
+            return self.held;
+        }
+        // the -1 to 1 range split into 2^bits steps
+        let step = 2.0 / 2f32.powf(bits);
+        ((self.held.0 / step).round() * step, (self.held.1 / step).round() * step)
+    }
+}
+
+// Reverse Feedback: the feedback gets chopped into windows one delay long, and each window is played
+// backwards while the next one is recorded. The first echo comes straight out of the buffer as normal,
+// but everything after it has been through here, so from the second echo on they're backwards
+// (and the ones after that forwards again, backwards again, and so on, since each pass flips them).
+// Unlike a plain reverse delay, which plays the dry input backwards, this leaves the first echo alone.
+// Recording a window before it can be played back costs a delay length, so the repeats after the
+// first come twice as far apart. Both ends of each window are faded so the joins don't click.
+struct Reverser {
+    // recording into current while previous plays backwards, then they swap
+    current: Vec<(f32, f32)>,
+    previous: Vec<(f32, f32)>,
+    position: usize,
+    fade: f32,
+}
+
+impl Reverser {
+    fn new(sample_rate: f32, delay_time: f32) -> Reverser {
+        // the windows only come in whole samples
+        let size = delay_samples(sample_rate, delay_time).round() as usize;
+        Reverser {
+            current: vec![(0.0, 0.0); size],
+            previous: vec![(0.0, 0.0); size],
+            position: 0,
+            // never more than half a window, or the fades in and out would overlap
+            fade: (REVERSE_FADE_TIME * sample_rate).min(size as f32 / 2.0).max(1.0),
+        }
+    }
+
+    fn process(&mut self, left: f32, right: f32) -> (f32, f32) {
+        let size = self.current.len();
+        self.current[self.position] = (left, right);
+        let (out_l, out_r) = self.previous[size - 1 - self.position];
+        let from_edge = self.position.min(size - 1 - self.position) as f32;
+        let gain = (from_edge / self.fade).min(1.0);
+        self.position += 1;
+        if self.position == size {
+            std::mem::swap(&mut self.current, &mut self.previous);
+            self.position = 0;
+        }
+        (out_l * gain, out_r * gain)
+    }
+}
+
+// Mix Lock's level matching: turns the wet up or down so it's as loud as the input, on average.
+// Along with the equal power crossfade in mix_samples_locked, that keeps the output about as loud as the input
+// whatever Dry/Wet is set to. It only adjusts while there's input, otherwise the tail would get turned down
+// the moment the playing stopped. It only goes 12dB either way, and moves slowly enough that it won't undo
+// the ducking or tremolo. Before the first echo comes back there's nothing to match, so it drifts back to
+// no change rather than turning the wet all the way up for when it does.
+struct LevelMatch {
+    input_power: f32,
+    wet_power: f32,
+    coeff: f32,
+    gain: f32,
+}
+
+impl LevelMatch {
+    fn new(sample_rate: f32) -> LevelMatch {
+        LevelMatch {
+            input_power: 0.0,
+            wet_power: 0.0,
+            coeff: smoothing_coeff(MIX_LOCK_TIME, sample_rate),
+            gain: 1.0,
+        }
+    }
+
+    // takes the input and the wet, returns the gain for the wet
+    fn process(&mut self, input: (f32, f32), wet: (f32, f32)) -> f32 {
+        self.input_power += ((input.0 * input.0 + input.1 * input.1) * 0.5 - self.input_power) * self.coeff;
+        self.wet_power += ((wet.0 * wet.0 + wet.1 * wet.1) * 0.5 - self.wet_power) * self.coeff;
+        if self.input_power > MIX_LOCK_SILENCE {
+            let target = if self.wet_power > MIX_LOCK_SILENCE {
+                (self.input_power / self.wet_power).sqrt().clamp(1.0 / MIX_LOCK_MAX_GAIN, MIX_LOCK_MAX_GAIN)
+            } else {
+                1.0
+            };
+            self.gain += (target - self.gain) * self.coeff;
+        }
+        self.gain
+    }
+}
+
+// Stereo's offset: a short delay on the right side of the wet, up to STEREO_MAX_OFFSET long.
+// It's only ever a whole number of samples, and it's always recording, so turning Stereo up
+// doesn't have to wait for it to fill
+struct Spread {
+    buffer: Vec<f32>,
+    position: usize,
+}
+
+impl Spread {
+    fn new(sample_rate: f32) -> Spread {
+        Spread {
+            buffer: vec![0.0; (STEREO_MAX_OFFSET * sample_rate).ceil() as usize + 1],
+            position: 0,
+        }
+    }
+
+    // offset is how far back to read, 0 to 1 of STEREO_MAX_OFFSET
+    fn process(&mut self, input: f32, offset: f32) -> f32 {
+        let size = self.buffer.len();
+        self.buffer[self.position] = input;
+        let behind = ((size - 1) as f32 * offset).round() as usize;
+        let output = self.buffer[(self.position + size - behind) % size];
+        self.position = (self.position + 1) % size;
+        output
+    }
+}
+
+// Turns the Feedback knob (MIN_FEEDBACK to 1) into how much of the buffer's output goes back in (0 to FEEDBACK_CEILING).
+// With the two constants as they are this works out to feedback_amt - 0.1, which is what it's always been
+fn loop_gain(feedback_amt: f32) -> f32 {
+    (feedback_amt - MIN_FEEDBACK).max(0.0) / (1.0 - MIN_FEEDBACK) * FEEDBACK_CEILING
+}
+
+// Motion: how much further back than the delay one side reads the delay line from. It wanders, slowly and at
+// random, between nothing and MOTION_MAX_OFFSET. Every so often it picks somewhere new and glides there, so the
+// pitch bends a few cents and back with no regular pattern to it, unlike an LFO. Each side has its own, so the two
+// drift apart (in mid/side it's mid and side that do). The seeds are fixed, so from set_sample_rate on it always
+// wanders the same way. It's the loop itself being read, so every echo goes through it again and wobbles a little
+// more than the one before, the way tape does. This only says where to read, DelayLine::read does the rest
+struct Drift {
+    // the furthest it can go, in samples
+    furthest: f32,
+    // where it's wandering to, 0 to 1 of the way to the furthest Motion allows
+    target: f32,
+    // how far back it's reading right now, in samples
+    offset: f32,
+    countdown: usize,
+    coeff: f32,
+    sample_rate: f32,
+    state: u32,
+}
+
+impl Drift {
+    fn new(sample_rate: f32, seed: u32) -> Drift {
+        Drift {
+            furthest: MOTION_MAX_OFFSET * sample_rate,
+            target: 0.0,
+            offset: 0.0,
+            countdown: 0,
+            coeff: smoothing_coeff(MOTION_GLIDE_TIME, sample_rate),
+            sample_rate,
+            state: seed,
+        }
+    }
+
+    // 0 to 1, xorshift like the BBD's noise
+    fn random(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state as f32 / u32::MAX as f32
+    }
+
+    // how many samples to add to the delay for this sample
+    fn process(&mut self, motion: f32) -> f32 {
+        if self.countdown == 0 {
+            self.target = self.random();
+            let interval = MOTION_SLOWEST + (MOTION_FASTEST - MOTION_SLOWEST) * motion;
+            self.countdown = (interval * self.sample_rate) as usize;
+        } else {
+            self.countdown -= 1;
+        }
+        // the target scales with Motion as it is now, so turning it down glides back to the delay as it's set
+        self.offset += (self.target * self.furthest * motion - self.offset) * self.coeff;
+        self.offset
+    }
+}
+
+// Scatter: every repeat comes back from a slightly different place, so the echoes land irregularly instead of
+// on a grid. Once every delay length it picks a random amount, anything up to SCATTER_MAX_SHARE of the delay
+// at 100%, and the loop reads that much less far back. Only ever less, so it never reads past the end of the
+// buffer. Jumping straight there would click, so it crossfades from the old place to the new one over
+// SCATTER_FADE_TIME (or the delay, if that's shorter). Unlike Motion nothing glides, so the pitch stays put.
+// The seed comes from the Scatter Seed knob, so the same seed always scatters the same way. At 0% it settles
+// back on the delay as it's set within one repeat, and from then on it's an ordinary delay
+struct Scatter {
+    // how early it was reading and how early it's going to, in samples
+    from: f32,
+    to: f32,
+    // 0 to 1 of the way from one to the other
+    fade: f32,
+    fade_samples: f32,
+    countdown: usize,
+    state: u32,
+}
+
+impl Scatter {
+    fn new(sample_rate: f32, seed: u32) -> Scatter {
+        Scatter {
+            from: 0.0,
+            to: 0.0,
+            fade: 1.0,
+            fade_samples: SCATTER_FADE_TIME * sample_rate,
+            countdown: 0,
+            state: seed,
+        }
+    }
+
+    // how early to read from, where it's going, and how far it's got there
+    fn process(&mut self, delay: f32, scatter: f32) -> (f32, f32, f32) {
+        if self.countdown == 0 {
+            self.from = self.to;
+            self.to = dither(&mut self.state).abs() * scatter * SCATTER_MAX_SHARE * delay;
+            self.fade = 0.0;
+            self.countdown = (delay as usize).max(1);
+        }
+        self.countdown -= 1;
+        // never longer than a delay, so it's always finished before the next one starts
+        self.fade = (self.fade + 1.0 / self.fade_samples.min(delay).max(1.0)).min(1.0);
+        if self.fade == 1.0 {
+            self.from = self.to;
+        }
+        (self.from, self.to, self.fade)
+    }
+}
+
+// which of the SCATTER_SEEDS the Scatter Seed knob is on, from 1
+fn scatter_seed_number(value: f32) -> u32 {
+    (value * (SCATTER_SEEDS - 1.0)).round() as u32 + 1
+}
+
+// and the xorshift state that starts from. Never 0, since xorshift would be stuck there
+fn scatter_seed(value: f32) -> u32 {
+    scatter_seed_number(value).wrapping_mul(0x9e37_79b9)
+}
+
+// -1 to 1, for Denormals' dither. xorshift like the BBD's noise. It only needs the state, not the
+// whole plugin, so process can call it while it's in the middle of reading the scratch buffers
+fn dither(state: &mut u32) -> f32 {
+    *state ^= *state << 13;
+    *state ^= *state >> 17;
+    *state ^= *state << 5;
+    (*state as f32 / u32::MAX as f32) * 2.0 - 1.0
+}
+
+// the subsonic filter, a 2nd order Butterworth high-pass. Starts out empty
+fn subsonic_filter(sample_rate: f32) -> Biquad {
+    Biquad::high_pass(sample_rate, SUBSONIC_CUTOFF, std::f32::consts::FRAC_1_SQRT_2)
+}
+
+// Mono Output's lo-fi filter. The cutoff can't go past Nyquist at very low sample rates
+fn mono_lofi_filter(sample_rate: f32) -> Biquad {
+    let cutoff = MONO_LOFI_CUTOFF.min(sample_rate * 0.45);
+    Biquad::low_pass(sample_rate, cutoff, std::f32::consts::FRAC_1_SQRT_2)
+}
+
+// Resonance's limiter: untouched up to RESONANCE_KNEE, then it bends over smoothly and never goes past 1
+fn limit_loop(sample: f32) -> f32 {
+    let level = sample.abs();
+    if level <= RESONANCE_KNEE {
+        sample
+    } else {
+        let over = (level - RESONANCE_KNEE) / (1.0 - RESONANCE_KNEE);
+        sample.signum() * (RESONANCE_KNEE + (1.0 - RESONANCE_KNEE) * over.tanh())
+    }
+}
+
+// the BBD's compander, applied to the feedback only
+fn compand(sample: f32) -> f32 {
+    sample / (1.0 + BBD_COMPANDING * sample.abs())
+}
+
+impl SillyDelay {
+    pub fn builder() -> SillyDelayBuilder {
+        SillyDelayBuilder {
+            sample_rate: 44100.0,
+            delay_ms: 2.0,
+            feedback: 0.1,
+            dry_wet: 1.0,
+        }
+    }
+
+    /// Current levels of the input, the delayed (wet) signal and the output.
+    ///
+    /// ```
+    /// use ::SillyDelay::SillyDelay;
+    ///
+    /// let mut delay = SillyDelay::builder().delay_ms(10.0).build().unwrap();
+    /// let input = [0.5; 1000];
+    /// let (mut out_l, mut out_r) = ([0.0; 1000], [0.0; 1000]);
+    /// delay.process_block(&input, &input, &mut out_l, &mut out_r);
+    /// assert_eq!(delay.meters().input, 0.5);
+    /// ```
+    pub fn meters(&self) -> Meters {
+        Meters {
+            input: self.input_meter.peak,
+            wet: self.wet_meter.peak,
+            output: self.output_meter.peak,
+            correlation: self.width.correlation(),
+        }
+    }
+
+    // How big delay_buffer is right now, in samples. It's remade whenever the delay time changes, and it's only
+    // ever as big as the current delay plus room for Motion and a couple of samples for reading between them.
+    // While a Clear is fading out it's still the old one (the new one is waiting in pending_buffers)
+    pub fn buffer_samples(&self) -> usize {
+        self.delay_buffer.capacity()
+    }
+
+    // the same in ms, at the current sample rate
+    pub fn buffer_ms(&self) -> f32 {
+        self.buffer_samples() as f32 / self.sample_rate * 1000.0
+    }
+
+    // the biggest delay_buffer can get at the current sample rate, ie. Delay Time all the way up (2 seconds,
+    // or less if MAX_DELAY_BUFFER_SIZE cuts it short)
+    pub fn max_buffer_samples(&self) -> usize {
+        let delay = delay_samples(self.sample_rate, Param::DelayTime.range().1);
+        DelayLine::size_for(delay + MOTION_MAX_OFFSET * self.sample_rate)
+    }
+
+    // For drawing what's in the delay: fills `out` with the last delay's worth of the buffer, oldest first, squeezed
+    // (or stretched) to however long `out` is, in mono. Each point is the loudest sample in its stretch, keeping its
+    // sign, so short peaks don't vanish when it's squeezed. It copies and nothing else, so it never allocates, and
+    // any length of `out` is fine (even 0). What's in the buffer is the loop as it goes round, so it's before the
+    // Attack, Stereo, tremolo and the rest that only the wet gets. In mid/side it's the mid.
+    // It borrows the plugin, so it can never run in the middle of process and can't see half a block. vst 0.1.0
+    // has no separate object for an editor, so a GUI on another thread has to reach the plugin through whatever
+    // lock it shares with the audio thread, and call this between blocks. It reads the whole delay, up to
+    // 2 seconds of it, so once per frame is plenty
+    pub fn buffer_snapshot(&self, out: &mut [f32]) {
+        let mid_side = self.buffer_mid_side;
+        self.delay_buffer.snapshot(out, |(a, b)| {
+            let (a, b) = (loop_to_f32(a), loop_to_f32(b));
+            if mid_side { a } else { (a + b) * 0.5 }
+        });
+    }
+
+    // Registers something to be told about every parameter change, with the value the plugin actually kept
+    // (after clamping), whether it came from the host, import_settings or anywhere else. Replaces any earlier one.
+    // It's called from set_parameter, which some hosts call from the audio thread, so it mustn't allocate or block.
+    // Boxing it here means set_parameter itself never has to.
+    pub fn set_parameter_listener(&mut self, listener: impl FnMut(Param, f32) + Send + 'static) {
+        self.parameter_listener = Some(Box::new(listener));
+    }
+
+    // clip lights: true if the input (or output) has gone over full scale in the last CLIP_HOLD_TIME
+    pub fn input_clipped(&self) -> bool {
+        self.input_meter.clip > 0
+    }
+
+    pub fn output_clipped(&self) -> bool {
+        self.output_meter.clip > 0
+    }
+
+    // How close the loop is to feeding back on itself forever, 0 to 1: the gain round the loop,
+    // where 1 would mean the echoes never die away. It comes from the settings, not the audio, so it's
+    // the worst case. Everything else in the loop (BBD, lo-fi, saturation, Network's rotation,
+    // Cross-Linked) only ever takes level away, and Repeat Limit can only cut it to 0. For a warning
+    // light, anything past about 0.9 is more than the Feedback knob alone can do
+    pub fn feedback_headroom_used(&self) -> f32 {
+        self.current_loop_gain()
+    }
+
+    /// Writes every parameter out as text, one `"Name" = value` per line (which also happens to be valid TOML),
+    /// so settings can be pasted somewhere and shared. Values are the same 0 to 1 the host sees, not ms or %.
+    /// This is just for people. The host saves its own state separately.
+    ///
+    /// ```
+    /// use ::SillyDelay::SillyDelay;
+    ///
+    /// let delay = SillyDelay::builder().dry_wet(0.25).build().unwrap();
+    /// let text = delay.export_settings();
+    /// assert!(text.lines().any(|line| line == "\"Dry/Wet\" = 0.25"));
+    /// ```
+    pub fn export_settings(&self) -> String {
+        let mut text = format!("{} v{}\n", SETTINGS_HEADER_V1, PARAMETER_VERSION);
+        for index in 0..self.get_info().parameters {
+            text += &format!("\"{}\" = {}\n", self.get_parameter_name(index), self.get_parameter(index));
+        }
+        text
+    }
+
+    /// Reads text written by export_settings. Blank lines and # comments are skipped, and parameters
+    /// that aren't mentioned are left alone. Everything is checked before anything is set, so a bad line
+    /// means nothing changes. Text exported before the Delay Time knobs went logarithmic (it starts with
+    /// the old header, with no version) has its delay times moved to where the new knobs give the same delay.
+    ///
+    /// ```
+    /// use ::SillyDelay::{SettingsError, SillyDelay};
+    ///
+    /// let mut delay = SillyDelay::builder().dry_wet(0.25).build().unwrap();
+    /// let text = delay.export_settings();
+    /// let mut other = SillyDelay::builder().build().unwrap();
+    /// other.import_settings(&text).unwrap();
+    /// assert_eq!(other.export_settings(), text);
+    ///
+    /// let bad = delay.import_settings("\"Dry/Wet\" = 0.5\n\"Feedback\" = loud");
+    /// assert_eq!(bad, Err(SettingsError::BadValue("loud".to_string())));
+    /// assert_eq!(delay.export_settings(), text);
+    /// ```
+    pub fn import_settings(&mut self, text: &str) -> Result<(), SettingsError> {
+        let linear_delay_times = text.lines().next().map(str::trim) == Some(SETTINGS_HEADER_V1);
+        let mut settings = Vec::new();
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (name, value) = match line.find('=') {
+                Some(equals) => (line[..equals].trim().trim_matches('"'), line[equals + 1..].trim()),
+                None => return Err(SettingsError::BadLine(line_number + 1)),
+            };
+            let index = (0..self.get_info().parameters)
+                .find(|&index| self.get_parameter_name(index) == name)
+                .ok_or_else(|| SettingsError::UnknownParameter(name.to_string()))?;
+            let value = value.parse::<f32>().ok()
+                .filter(|value| (0.0..=1.0).contains(value))
+                .ok_or_else(|| SettingsError::BadValue(value.to_string()))?;
+            // the old knobs were 0 to 1 of 2000ms in a straight line, like delay_time still is
+            let value = match Param::from_i32(index) {
+                Some(Param::DelayTime | Param::DelayTime2) if linear_delay_times => delay_to_knob(value),
+                _ => value,
+            };
+
+            settings.push((index, value));
+        }
+
+        for (index, value) in settings {
+            self.set_parameter(index, value);
+        }
+        Ok(())
+    }
+
+    // Puts every parameter back to Param::default(), the same way the host would set them (so the listener
+    // hears about each one), then rebuilds the buffers so nothing from before is left ringing.
+    // Everything else (sample rate, listener, meters) is left alone.
+    pub fn reset_to_defaults(&mut self) {
+        for param in Param::ALL {
+            self.set_parameter(param.to_i32(), param.default());
+        }
+        // set_sample_rate builds everything fresh, which also cancels any Clear the delay time change started
+        self.set_sample_rate(self.sample_rate);
+    }
+
+    // Remembers every parameter as it is now, for Morph to go to and from. Storing one doesn't change anything
+    // until Morph next moves
+    pub fn store_snapshot(&mut self, snapshot: Snapshot) {
+        let mut values = [0.0; Param::ALL.len()];
+        for (value, param) in values.iter_mut().zip(Param::ALL) {
+            *value = self.get_parameter(param.to_i32());
+        }
+        self.snapshots[snapshot as usize] = Some(values);
+    }
+
+    // Sets every parameter that morphs (see Param::morphs) to somewhere between A and B, in a straight line.
+    // The rest stay wherever they are. Nothing happens until both snapshots have been stored
+    fn apply_morph(&mut self) {
+        if let [Some(a), Some(b)] = self.snapshots {
+            for (index, &param) in Param::ALL.iter().enumerate() {
+                if param.morphs() {
+                    self.set_parameter(param.to_i32(), a[index] + (b[index] - a[index]) * self.morph);
+                }
+            }
+        }
+    }
+
+    // Handles one raw MIDI message. Control changes listed in MIDI_CC_MAP set their parameter, and the host
+    // is told so its knob follows. Notes are for Note Delay. Everything else is ignored. process_events sends every
+    // MIDI message here, and it's public so MIDI can be fed in without a host too
+    pub fn midi_event(&mut self, data: [u8; 3]) {
+        // 0x80 to 0x8F is a note off and 0x90 to 0x9F a note on, on channels 1 to 16. A note on with
+        // a velocity of 0 is a note off too
+        match data[0] & 0xf0 {
+            0x90 if data[2] > 0 => return self.note_event(data[1], true),
+            0x80 | 0x90 => return self.note_event(data[1], false),
+            // 0xB0 to 0xBF is a control change
+            0xb0 => (),
+            _ => return,
+        }
+        if let Some(&(_, param)) = MIDI_CC_MAP.iter().find(|&&(cc, _)| cc == data[1]) {
+            let index = param.to_i32();
+            self.set_parameter(index, f32::from(data[2].min(127)) / 127.0);
+            let kept = self.get_parameter(index);
+            if let Some(host) = &mut self.host {
+                host.automate(index, kept);
+            }
+        }
+    }
+
+    // only clears the buffer if the delay time actually moved, eg. not for a note under a newer one being let go
+    fn note_event(&mut self, note: u8, on: bool) {
+        let before = self.current_delay_time();
+        if on {
+            self.held_notes.press(note);
+        } else {
+            self.held_notes.release(note);
+        }
+        if self.current_delay_time() != before {
+            self.clear_buffer();
+        }
+    }
+
+    // How much of the real-time budget processing is taking, in % (see CpuMeter). Only with the "cpu-usage"
+    // feature, so without it nothing gets timed at all. Handy for seeing what the heavier settings cost
+    #[cfg(feature = "cpu-usage")]
+    pub fn cpu_usage(&self) -> f32 {
+        self.cpu_meter.usage
+    }
+
+    // the most recent diagnostics, oldest first
+    #[cfg(feature = "diagnostics")]
+    pub fn diagnostics(&self) -> impl Iterator<Item = Diagnostic> + '_ {
+        self.diagnostics.iter()
+    }
+
+    // Without the "diagnostics" feature this does nothing and compiles away to nothing
+    #[cfg_attr(not(feature = "diagnostics"), allow(unused_variables))]
+    fn report(&mut self, event: Diagnostic) {
+        #[cfg(feature = "diagnostics")]
+        self.diagnostics.push(event);
+    }
+
+    // Scratch buffers are sized to the host's maximum block size, outside of process whenever possible.
+    // Not every host reports one before it starts processing (or sticks to it), so process will also
+    // grow them if a block turns up that doesn't fit. That one time it allocates on the audio thread.
+    fn resize_scratch(&mut self, size: usize) {
+        self.scratch_l.resize(size, 0.0);
+        self.scratch_r.resize(size, 0.0);
+        self.sidechain_l.resize(size, 0.0);
+        self.sidechain_r.resize(size, 0.0);
+    }
+
+    // Throwing away delay_buffer while it's full of sound makes a click, since the tail stops dead.
+    // So the new buffer waits in pending_buffers while process fades the wet out over CLEAR_FADE_TIME,
+    // then gets swapped in and the wet fades back up. The new buffer is made here rather than
+    // in process so the audio thread doesn't have to allocate it. The network's second line gets
+    // cleared along with it, otherwise its half of the tail would carry on.
+    // If a clear is already on its way this only changes what it'll swap in: the fade carries on from wherever
+    // it's got to instead of starting again. So however fast the delay time is automated there's only ever
+    // one fade going, and it ends with the latest delay time. Nothing has been recorded into the pending
+    // buffers yet, so if they're already the right size they're kept rather than made all over again.
+    fn clear_buffer(&mut self) {
+        let sizes = (
+            delay_samples(self.sample_rate, self.current_delay_time()),
+            delay_samples(self.sample_rate, self.delay_time_2),
+        );
+        let reusable = matches!(&self.pending_buffers,
+            Some((delay_buffer, network_buffer)) if (delay_buffer.delay(), network_buffer.delay()) == sizes);
+        if !reusable {
+            self.report(Diagnostic::BuffersRebuilt);
+            self.pending_buffers = Some((
+                reload_delay_buffer(self.sample_rate, self.current_delay_time()),
+                reload_delay_buffer(self.sample_rate, self.delay_time_2),
+            ));
+        }
+        // anything that changes the delay time comes through here, and the BBD's tone depends on it.
+        // So does the reverser's window. Whatever it had recorded is gone, but the wet is fading out anyway
+        let (sample_rate, delay_time) = (self.sample_rate, self.current_delay_time());
+        self.bbd.set_delay_time(sample_rate, delay_time);
+        self.reverser = Reverser::new(sample_rate, delay_time);
+    }
+
+    // Slapback mode doesn't touch delay_time, feedback_amt or dry_wet, it just ignores them while it's on.
+    // So the host still sees (and saves) whatever the knobs are set to, plus the Slapback switch,
+    // and turning slapback off goes straight back to the knobs. These are the values actually in use.
+    fn current_delay_time(&self) -> f32 {
+        if self.slapback {
+            SLAPBACK_DELAY_TIME
+        } else {
+            self.note_delay_time().or_else(|| self.hz_delay_time()).unwrap_or_else(|| self.quantized_delay_time())
+        }
+    }
+
+    // Note Delay: while a note is held the delay is one cycle of it, so with lots of feedback the loop rings at that
+    // pitch (a plucked string, more or less). The newest held note wins, and letting go goes back to the one held
+    // before it, or the Delay Time knob once there are none. Like slapback the knob itself isn't touched.
+    // The delay line reads between samples, so it's in tune all the way up: A4 at 48k is 109.09 samples
+    fn note_delay_time(&self) -> Option<f32> {
+        if !self.note_delay {
+            return None;
+        }
+        let (min, max) = Param::DelayTime.range();
+        // delay times are 0 to 1 of 2 seconds
+        self.held_notes.last().map(|note| (0.5 / note_to_hz(f32::from(note))).clamp(min, max))
+    }
+
+    // Delay in Hz: the Delay Time knob picks the pitch the loop rings at, from TUNED_LOWEST to TUNED_HIGHEST, and the
+    // delay is one cycle of it, sample_rate / freq samples. Like Note Delay it's in tune because the delay line reads
+    // between samples. Delay Quantize's grid is in ms, so it's left out. A held note (with Note Delay on) still wins
+    fn hz_delay_time(&self) -> Option<f32> {
+        if !self.delay_in_hz {
+            return None;
+        }
+        let (min, max) = Param::DelayTime.range();
+        Some((0.5 / knob_to_hz(self.delay_knob)).clamp(min, max))
+    }
+
+    // whether the delays are running on mid and side, from Stereo Mode, Channel Mode or Side Only
+    fn mid_side_on(&self) -> bool {
+        self.mid_side || self.channel_mode == ChannelMode::MidSide || self.side_only
+    }
+
+    // For anything that can turn mid/side on or off. Whatever is in the buffer (and going round the feedback)
+    // is in the other format, so it has to go, the same way a delay time change does: it fades out and
+    // the buffer and feedback start again empty in the new format (see buffer_mid_side)
+    fn change_mid_side(&mut self, change: impl FnOnce(&mut SillyDelay)) {
+        let was_mid_side = self.mid_side_on();
+        change(self);
+        if self.mid_side_on() != was_mid_side {
+            self.clear_buffer();
+        }
+    }
+
+    // the grid Delay Quantize snaps to in ms, 0 for off
+    fn quantize_grid(&self) -> f32 {
+        QUANTIZE_GRIDS[(self.quantize * (QUANTIZE_GRIDS.len() - 1) as f32).round() as usize]
+    }
+
+    // Delay Time snapped to the nearest point on the grid, for quick rhythmic delays without tempo sync.
+    // Like delay_time itself it's 0 to 1 of 2000ms. It never snaps down to 0, the shortest it goes is one grid step.
+    // The knob keeps its own value, so turning quantize off goes back to exactly where it was.
+    fn quantized_delay_time(&self) -> f32 {
+        let grid = self.quantize_grid();
+        if grid == 0.0 {
+            return self.delay_time;
+        }
+        let ms = ((self.delay_time * 2000.0 / grid).round() * grid).clamp(grid, 2000.0);
+        ms / 2000.0
+    }
+
+    fn current_feedback_amt(&self) -> f32 {
+        if self.slapback { MIN_FEEDBACK } else { self.feedback_amt }
+    }
+
+    // How much of the buffer's output goes back in, from Feedback and Resonance.
+    // Resonance: on purpose, this takes the loop right up to the edge of running away, for drones and
+    // endless sustain. Normally the feedback tops out at FEEDBACK_CEILING, but Resonance can take
+    // it up to RESONANCE_CEILING. That's still below 1, so a single hit always dies away eventually,
+    // but anything that keeps playing piles up in the loop. So with Resonance up there's also a
+    // soft-knee limiter in the loop (see limit_loop), which keeps it around full scale instead of
+    // letting it grow to a few hundred times the input.
+    // String Mode has its own, see STRING_LOOP_GAIN
+    fn current_loop_gain(&self) -> f32 {
+        let loop_gain = if self.string_mode { STRING_LOOP_GAIN } else { loop_gain(self.current_feedback_amt()) };
+        (loop_gain + (RESONANCE_CEILING - loop_gain) * self.resonance) * self.short_delay_gain()
+    }
+
+    // Short Delay Guard. At a few ms each trip round the loop is so short that the feedback turns into a pitched,
+    // metallic ring, and it's easy to get there by accident. With the guard on the loop gain is scaled by
+    // delay / SHORT_DELAY_GUARD_TIME below it, so it fades away gradually as the delay gets shorter rather than
+    // switching off at a point. With Network on the shorter of the two delays counts. Off (the default) leaves the
+    // feedback alone, for when the ringing is the point (flanging, comb filters, Resonance)
+    fn short_delay_gain(&self) -> f32 {
+        if !self.short_delay_guard {
+            return 1.0;
+        }
+        let delay_time = if self.network { self.current_delay_time().min(self.delay_time_2) } else { self.current_delay_time() };
+        (delay_time * 2.0 / SHORT_DELAY_GUARD_TIME).min(1.0)
+    }
+
+    fn current_dry_wet(&self) -> f32 {
+        if self.slapback { SLAPBACK_DRY_WET } else { self.dry_wet }
+    }
+
+    // Whether process can skip the delay altogether: Idle When Dry is on and none of the wet is in the mix.
+    // Off keeps it running underneath, so turning Dry/Wet back up brings back a tail that carried on all along.
+    // On saves the CPU, but whatever was ringing when it stopped is thrown away (see set_parameter),
+    // so turning it back up starts from an empty buffer
+    fn idle(&self) -> bool {
+        self.idle_when_dry && self.current_dry_wet() == 0.0
+    }
+
+    // String Mode: Karplus-Strong. The loop is tuned (with Delay in Hz or Note Delay), the gain round it is
+    // STRING_LOOP_GAIN, and the feedback goes through a one-pole low-pass, from STRING_BRIGHTEST down to STRING_DARKEST
+    // as String Damping goes up. So whatever comes in (a hit, a pick, a burst of noise) rings at the loop's pitch,
+    // and loses its top end first, like a plucked string. The low-pass delays what goes round a little, which
+    // would put it flat, so the loop reads that much less far back. This gives the low-pass's coefficient and
+    // that lag in samples, worked out at the loop's pitch. Up past a few kHz the lag is more than the delay,
+    // so the read is held at 1 sample and the string ends up flat
+    fn string_filter(&self) -> (f32, f32) {
+        let cutoff = lin_to_log(self.string_damping, STRING_BRIGHTEST, STRING_DARKEST);
+        let coeff = smoothing_coeff(1.0 / (2.0 * std::f32::consts::PI * cutoff), self.sample_rate);
+        let (pole, w) = (1.0 - coeff, 2.0 * std::f32::consts::PI / self.delay_buffer.delay());
+        (coeff, (pole * w.sin()).atan2(1.0 - pole * w.cos()) / w)
+    }
+
+    // bit depth the crusher rounds to, or 0 for not at all
+    fn crush_bits(&self) -> f32 {
+        if self.bits == 0.0 { 0.0 } else { MAX_CRUSH_BITS - self.bits * (MAX_CRUSH_BITS - MIN_CRUSH_BITS) }
+    }
+
+    fn downsample_factor(&self) -> usize {
+        1 + (self.downsample * (MAX_DOWNSAMPLE - 1.0)).round() as usize
+    }
+
+    // how many echoes to let through, None for no limit. Steps of 1/16 from 1 echo up to 16, and the top is no limit
+    fn repeat_count(&self) -> Option<usize> {
+        let count = 1 + (self.repeat_limit * MAX_REPEAT_LIMIT).round() as usize;
+        if count > MAX_REPEAT_LIMIT as usize { None } else { Some(count) }
+    }
+
+    // exponential, so every bit of the knob feels like the same amount of change
+    fn tremolo_rate_hz(&self) -> f32 {
+        lin_to_log(self.tremolo_rate, MIN_TREMOLO_RATE, MAX_TREMOLO_RATE)
+    }
+
+    // Phase Rotate's centre frequency. Turning it up moves the centre down (exponentially, so it feels even),
+    // which rotates more of the spectrum
+    fn phase_rotate_freq(&self) -> f32 {
+        lin_to_log(self.phase_rotate, PHASE_ROTATE_MAX_FREQ, PHASE_ROTATE_MIN_FREQ)
+    }
+
+    fn retune_phase_rotator(&mut self) {
+        let (sample_rate, centre) = (self.sample_rate, self.phase_rotate_freq());
+        self.phase_rotator.set_all_pass(sample_rate, centre, std::f32::consts::FRAC_1_SQRT_2);
+    }
+
+    // Natural Damping's cutoff: from DAMPING_BRIGHTEST with no feedback down to DAMPING_DARKEST with it all the way up,
+    // exponentially like Phase Rotate. It's the Feedback knob rather than what's in use, since Slapback has no feedback
+    // to darken anyway. Kept under half the sample rate for the same reason as the BBD's
+    fn damping_cutoff(&self) -> f32 {
+        let amount = (self.feedback_amt - MIN_FEEDBACK).max(0.0) / (1.0 - MIN_FEEDBACK);
+        lin_to_log(amount, DAMPING_BRIGHTEST, DAMPING_DARKEST).min(self.sample_rate * 0.45)
+    }
+
+    // keeps the filters' memory, so turning Feedback while it's running doesn't click
+    fn retune_damping(&mut self) {
+        let (sample_rate, cutoff) = (self.sample_rate, self.damping_cutoff());
+        self.damping_l.set_low_pass(sample_rate, cutoff, std::f32::consts::FRAC_1_SQRT_2);
+        self.damping_r.set_low_pass(sample_rate, cutoff, std::f32::consts::FRAC_1_SQRT_2);
+    }
+
+    // Age's low-pass, exponentially from AGE_BRIGHTEST to AGE_DARKEST
+    fn retune_age_filters(&mut self) {
+        let sample_rate = self.sample_rate;
+        let cutoff = lin_to_log(self.age, AGE_BRIGHTEST, AGE_DARKEST).min(sample_rate * 0.45);
+        self.age_filter_l.set_low_pass(sample_rate, cutoff, std::f32::consts::FRAC_1_SQRT_2);
+        self.age_filter_r.set_low_pass(sample_rate, cutoff, std::f32::consts::FRAC_1_SQRT_2);
+    }
+
+    // Everything whose coefficients come from a parameter and the sample rate. They start out at all zeros
+    // (which is silence), so default(), new() and set_sample_rate all need this
+    fn retune_filters(&mut self) {
+        self.retune_phase_rotator();
+        self.retune_damping();
+        self.retune_age_filters();
+    }
+
+    // stutter slice length in seconds
+    fn stutter_time(&self) -> f32 {
+        MIN_STUTTER_TIME + self.stutter_length * (MAX_STUTTER_TIME - MIN_STUTTER_TIME)
+    }
+
+    // loop length in seconds
+    fn loop_time(&self) -> f32 {
+        MIN_LOOP_TIME + self.loop_length * (MAX_LOOP_TIME - MIN_LOOP_TIME)
+    }
+
+    fn stutter_repeat_count(&self) -> usize {
+        1 + (self.stutter_repeats * (MAX_STUTTER_REPEATS - 1.0)).round() as usize
+    }
+
+    // meters are rebuilt rather than just zeroed so hold and release follow the sample rate.
+    // This turns the clip lights off too
+    fn reset_meters(&mut self) {
+        self.input_meter = PeakMeter::new(self.sample_rate);
+        self.wet_meter = PeakMeter::new(self.sample_rate);
+        self.output_meter = PeakMeter::new(self.sample_rate);
+    }
+
+    /// Runs a block without a host, e.g. for rendering a file offline or poking at the sound in a test.
+    /// The inputs are copied into scratch just like process does, and then it goes through exactly the
+    /// same code, so the output is identical. If the slices aren't all the same length, only as many
+    /// samples as the shortest one are processed.
+    ///
+    /// ```
+    /// use ::SillyDelay::SillyDelay;
+    ///
+    /// // 1ms at 44.1k is 44.1 samples, so an impulse comes back out 44 samples later
+    /// let mut delay = SillyDelay::builder().delay_ms(1.0).dry_wet(1.0).build().unwrap();
+    /// let mut input = vec![0.0; 512];
+    /// input[256] = 1.0;
+    /// let (mut out_l, mut out_r) = (vec![0.0; 512], vec![0.0; 512]);
+    /// delay.process_block(&input, &input, &mut out_l, &mut out_r);
+    /// let loudest = out_l.iter().enumerate().fold(0, |loudest, (i, x)| if x.abs() > out_l[loudest].abs() { i } else { loudest });
+    /// assert_eq!(loudest, 256 + 44);
+    /// ```
+    pub fn process_block(&mut self, input_l: &[f32], input_r: &[f32], out_l: &mut [f32], out_r: &mut [f32]) {
+        let samples = input_l.len().min(input_r.len()).min(out_l.len()).min(out_r.len());
+        if samples > self.scratch_l.len() {
+            self.report(Diagnostic::ScratchGrown(samples));
+            self.resize_scratch(samples);
+        }
+        self.scratch_l[..samples].copy_from_slice(&input_l[..samples]);
+        self.scratch_r[..samples].copy_from_slice(&input_r[..samples]);
+        self.has_sidechain = false;
+        self.process_scratch(&mut out_l[..samples], &mut out_r[..samples]);
+    }
+
+    // everything process and process_block have in common. The input is already in scratch_l/scratch_r,
+    // and there's one output sample for each input sample.
+    fn process_scratch(&mut self, out_l: &mut [f32], out_r: &mut [f32]) {
+        #[cfg(feature = "cpu-usage")]
+        let started = std::time::Instant::now();
+        let samples = out_l.len().min(out_r.len());
+        let dry_wet = self.current_dry_wet();
+        let crush_bits = self.crush_bits();
+        let downsample_factor = self.downsample_factor();
+        let loop_gain = self.current_loop_gain();
+        // Repeat Limit in samples: echo N comes out N delay lengths after the input, so anything coming out
+        // of the buffer after that would only be making echo N+1. The cut is halfway between echo N-1 and echo N,
+        // since a delay that isn't a whole number of samples smears each echo across a sample or two
+        let repeat_samples = self.repeat_count()
+            .map(|count| ((count as f32 - 0.5) * self.delay_buffer.delay()) as usize);
+        // Age's wow and flutter goes on top of whatever Motion is set to
+        let motion = (self.motion + self.age * AGE_MAX_MOTION).min(1.0);
+        // how far through its cycle the tremolo moves each sample
+        let tremolo_step = self.tremolo_rate_hz() / self.sample_rate;
+        let correlation_floor = if self.mono_safe { Some(self.correlation_floor * 2.0 - 1.0) } else { None };
+        let mut mid_side = self.buffer_mid_side;
+        let (rotate_sin, rotate_cos) = (self.pan_rotate * PAN_ROTATE_MAX_STEP).sin_cos();
+        let (rotate_sin, rotate_cos) = (LoopSample::from(rotate_sin), LoopSample::from(rotate_cos));
+        // whether anything colours what comes out of the buffer on its way to the feedback, or the feedback itself.
+        // All of those work in f32, see LoopSample
+        let colours_output = self.bbd_on || crush_bits > 0.0 || downsample_factor > 1 || self.age > 0.0;
+        let colours_feedback = self.natural_damping || self.bbd_on || self.saturation != Saturation::Off
+            || self.age > 0.0 || self.resonance > 0.0 || self.post_mix_feedback || self.reverse_feedback || self.string_mode;
+        let (string_coeff, string_lag) = if self.string_mode { self.string_filter() } else { (1.0, 0.0) };
+        let idle = self.idle();
+        let freeze_step = 1.0 / (FREEZE_FADE_TIME * self.sample_rate);
+
+        // swap or sum the inputs if asked to. Everything from here on, dry included, gets the routed input
+        if self.input_routing != InputRouting::Normal {
+            for (in_l_s, in_r_s) in self.scratch_l[..samples].iter_mut().zip(&mut self.scratch_r[..samples]) {
+                let (routed_l, routed_r) = self.input_routing.route(*in_l_s, *in_r_s);
+                *in_l_s = routed_l;
+                *in_r_s = routed_r;
+            }
+        }
+
+        // each zip adds to a tuple, going outwards, hence the weird (((x,x),x)x) thing.
+        // sidenote: l / r is left, right; s is sample.
+        for ((((&in_l_s, &in_r_s), (&side_l_s, &side_r_s)), out_l_s), out_r_s) in self.scratch_l[..samples]
+        .iter()
+        .zip(&self.scratch_r[..samples])
+        .zip(self.sidechain_l[..samples].iter().zip(&self.sidechain_r[..samples]))
+        .zip(out_l.iter_mut())
+        .zip(out_r.iter_mut())
+        {
+            // the test signal takes the input's place before anything else sees it, dry included
+            let (in_l_s, in_r_s) = match self.test_signal {
+                TestSignal::Off => (in_l_s, in_r_s),
+                TestSignal::Tone => {
+                    self.test_phase = (self.test_phase + TEST_TONE_FREQ / self.sample_rate).fract();
+                    let sample = (self.test_phase * 2.0 * std::f32::consts::PI).sin() * TEST_SIGNAL_LEVEL;
+                    (sample, sample)
+                },
+                TestSignal::Impulse => {
+                    let sample = if self.test_countdown == 0 {
+                        // counting this one, so the next click is exactly TEST_IMPULSE_TIME later
+                        self.test_countdown = ((TEST_IMPULSE_TIME * self.sample_rate) as usize).saturating_sub(1);
+                        TEST_SIGNAL_LEVEL
+                    } else {
+                        self.test_countdown -= 1;
+                        0.0
+                    };
+                    (sample, sample)
+                },
+            };
+            let (in_l_s, in_r_s) = (in_l_s * self.input_gain, in_r_s * self.input_gain);
+            self.input_meter.update(in_l_s, in_r_s);
+
+            // The ducker listens to the input as it arrives, but everything else gets it lookahead_time later.
+            // So the wet is already on its way down by the time a loud hit reaches the output.
+            // With no lookahead the buffer has nothing in it and the input goes straight through.
+            let (duck_l, duck_r) = match self.duck_source {
+                DuckSource::Dry => self.last_dry,
+                DuckSource::Output => self.last_output,
+                DuckSource::Sidechain if self.has_sidechain => (side_l_s, side_r_s),
+                _ => (in_l_s, in_r_s),
+            };
+            let (duck_env_l, duck_env_r) = if self.true_stereo {
+                (self.ducker.process(duck_l, duck_l), self.ducker_r.process(duck_r, duck_r))
+            } else {
+                let env = self.ducker.process(duck_l, duck_r);
+                (env, env)
+            };
+            let (duck_gain_l, duck_gain_r) = (1.0 - self.duck_depth * duck_env_l, 1.0 - self.duck_depth * duck_env_r);
+            let (in_l_s, in_r_s) = if self.lookahead_buffer.capacity() > 0 {
+                self.lookahead_buffer.add((in_l_s, in_r_s)).ok().unwrap().unwrap_or((0.0, 0.0))
+            } else {
+                (in_l_s, in_r_s)
+            };
+
+            // Repeat Limit. There's only one buffer that everything recirculates through, so there's no telling
+            // which echo a sample belongs to. Instead it goes by how long it's been since there was any input:
+            // after N delay lengths of silence the feedback is cut and whatever's left is the last echo.
+            // For a single hit that's exactly N echoes. Anything that keeps playing keeps the feedback going,
+            // and the count only starts once it stops. In True Stereo each side counts for itself.
+            let (level_l, level_r) = if self.true_stereo {
+                (in_l_s.abs(), in_r_s.abs())
+            } else {
+                (in_l_s.abs().max(in_r_s.abs()), 0.0)
+            };
+            self.since_input = if level_l > REPEAT_LIMIT_SILENCE { 0 } else { self.since_input.saturating_add(1) };
+            self.since_input_r = if level_r > REPEAT_LIMIT_SILENCE { 0 } else { self.since_input_r.saturating_add(1) };
+            let repeat_gain = |since_input: usize| match repeat_samples {
+                Some(repeat_samples) if since_input >= repeat_samples => 0.0,
+                _ => loop_gain,
+            };
+            let feedback_gain_l = repeat_gain(self.since_input);
+            let feedback_gain_r = if self.true_stereo { repeat_gain(self.since_input_r) } else { feedback_gain_l };
+
+            // in mid/side mode the buffer holds mid and side instead of left and right,
+            // so the feedback below is also worked out on mid and side
+            let (in_a, in_b) = if mid_side {
+                to_mid_side(in_l_s, in_r_s)
+            } else {
+                (in_l_s, in_r_s)
+            };
+            // Side Only: nothing from the mid goes in, so there's nothing in the mid to come out either
+            let in_a = if self.side_only { 0.0 } else { in_a };
+
+            // fade the wet out if a clear is waiting, and swap the buffer once it's silent. Otherwise fade back in
+            if self.pending_buffers.is_some() {
+                self.clear_gain -= self.clear_step;
+                if self.clear_gain <= 0.0 {
+                    self.clear_gain = 0.0;
+                    if let Some((delay_buffer, network_buffer)) = self.pending_buffers.take() {
+                        self.delay_buffer = delay_buffer;
+                        self.network_buffer = network_buffer;
+                    }
+                    // the new buffer is empty, so it can start out in whatever format is wanted now
+                    mid_side = self.mid_side_on();
+                    self.buffer_mid_side = mid_side;
+                    // whatever was frozen is gone, so if Freeze is still on it fades in again and freezes the new buffer
+                    self.freeze_mix = 0.0;
+                }
+            } else {
+                self.clear_gain = (self.clear_gain + self.clear_step).min(1.0);
+            }
+
+            // The input goes into the buffer with the same fade. Otherwise a fresh buffer would start recording
+            // wherever the input happened to be, and play that step back one delay later, long after the wet
+            // has faded back up. Outside a clear this is 1, so nothing changes
+            let (in_a, in_b) = (in_a * self.clear_gain, in_b * self.clear_gain);
+            // and Freeze shuts the input out, see below
+            let (in_a, in_b) = (in_a * (1.0 - self.freeze_mix), in_b * (1.0 - self.freeze_mix));
+            let (loop_in_a, loop_in_b) = (LoopSample::from(in_a), LoopSample::from(in_b));
+
+            // the network's second line, which runs alongside delay_buffer (below) and works the same way
+            let (net_l, net_r) = if self.network && !idle {
+                let delay = self.network_buffer.delay();
+                self.network_buffer.read((delay, delay), self.interpolation)
+            } else {
+                (0.0, 0.0)
+            };
+
+            // Idle When Dry: with none of the wet in the mix there's nothing to hear from any of this, so it's all
+            // skipped and the wet is silence. The buffer and everything after it just stop where they are
+            let (temp_l, temp_r) = if idle {
+                (0.0, 0.0)
+            } else {
+                // What went in one delay ago, plus however far Motion has each side wandering (see Drift).
+                // This sample's input only goes in once its feedback has been worked out (below), so every trip
+                // round the loop takes exactly one delay, no more
+                // Scatter brings it back a bit early (see Scatter), which only ever means reading less far back,
+                // so it can't go past the end of the buffer. While it's moving somewhere new it reads both and crossfades
+                // String Mode's low-pass needs it a little earlier too, see string_filter
+                let delay = self.delay_buffer.delay();
+                let drift = (self.drift_l.process(motion), self.drift_r.process(motion));
+                let (early, earlier, fade) = self.scatterer.process(delay, self.scatter);
+                let read = |early: f32| {
+                    let back = delay - string_lag - early;
+                    self.delay_buffer.read((back + drift.0, back + drift.1), self.interpolation)
+                };
+                let (temp_a, temp_b) = if early == earlier {
+                    read(early)
+                } else {
+                    let ((from_a, from_b), (to_a, to_b)) = (read(early), read(earlier));
+                    let fade = LoopSample::from(fade);
+                    (from_a + (to_a - from_a) * fade, from_b + (to_b - from_b) * fade)
+                };
+
+                // Network mode: a tiny feedback delay network. The outputs of the two lines go through
+                // a 2x2 rotation (sum and difference, scaled by 1/sqrt(2)). The sum goes on as the wet and
+                // back into delay_buffer, the difference goes back into network_buffer.
+                // The rotation doesn't add or remove any energy, so as long as the feedback is below 1
+                // (it always is, see FEEDBACK_CEILING) the tail dies away instead of building up.
+                // With two different delay times the echoes smear into something more like a reverb.
+                let (mut net_fb_l, mut net_fb_r): (LoopSample, LoopSample) = (0.0, 0.0);
+                let (exact_a, exact_b) = if self.network {
+                    let scale = LoopSample::from(std::f32::consts::FRAC_1_SQRT_2);
+                    net_fb_l = (temp_a - net_l) * scale * LoopSample::from(feedback_gain_l);
+                    net_fb_r = (temp_b - net_r) * scale * LoopSample::from(feedback_gain_r);
+                    ((temp_a + net_l) * scale, (temp_b + net_r) * scale)
+                } else {
+                    (temp_a, temp_b)
+                };
+                // from here on what we hear is f32. exact_a and exact_b are kept for the feedback, see LoopSample
+                let (temp_a, temp_b) = (loop_to_f32(exact_a), loop_to_f32(exact_b));
+
+                // the BBD colours everything coming out of the buffer, feedback included
+                let (temp_a, temp_b) = if self.bbd_on {
+                    self.bbd.process(temp_a, temp_b)
+                } else {
+                    (temp_a, temp_b)
+                };
+
+                // same for the lo-fi section, so the repeats get crunchier each time round
+                let (temp_a, temp_b) = if crush_bits > 0.0 || downsample_factor > 1 {
+                    self.crusher.process(temp_a, temp_b, crush_bits, downsample_factor)
+                } else {
+                    (temp_a, temp_b)
+                };
+
+                // Age: darker and hissier, on everything out of the buffer like the BBD, so it builds up with each repeat
+                let (temp_a, temp_b) = if self.age > 0.0 {
+                    let noise_level = AGE_NOISE_LEVEL * self.age;
+                    (
+                        self.age_filter_l.process(temp_a) + dither(&mut self.age_noise_state) * noise_level,
+                        self.age_filter_r.process(temp_b) + dither(&mut self.age_noise_state) * noise_level,
+                    )
+                } else {
+                    (temp_a, temp_b)
+                };
+
+                // add what came out of delay_buffer into feedback variables
+                // current_loop_gain() always ensures the value is between
+                // 0 and FEEDBACK_CEILING - to prevent, well, too much feedback (that's feedback_gain, unless Repeat Limit
+                // has cut it or Resonance has pushed it higher)
+                // Feedback Tap. Pre-Mix (the default, and how it's always been) feeds back what came out of the buffer,
+                // so only echoes go round again. Post-Mix feeds back the dry/wet mix instead, as it was a sample ago,
+                // so some of the dry goes round too and every echo has the input mixed back in, as much as Dry/Wet
+                // leaves of it. The wet in it has been through the whole wet path (smear, Stereo, tremolo, ducking...),
+                // which then happens again on every repeat. It fades with a Clear like the input does
+                let (tap_a, tap_b) = if self.post_mix_feedback {
+                    let (mix_l, mix_r) = (self.last_mix.0 * self.clear_gain, self.last_mix.1 * self.clear_gain);
+                    let (mix_a, mix_b) = if mid_side { to_mid_side(mix_l, mix_r) } else { (mix_l, mix_r) };
+                    (LoopSample::from(if self.side_only { 0.0 } else { mix_a }), LoopSample::from(mix_b))
+                } else if colours_output {
+                    (LoopSample::from(temp_a), LoopSample::from(temp_b))
+                } else {
+                    (exact_a, exact_b)
+                };
+                let mut fb_l = tap_a * LoopSample::from(feedback_gain_l);
+                let mut fb_r = tap_b * LoopSample::from(feedback_gain_r);
+                (fb_l, fb_r) = if colours_feedback {
+                    let (mut fb_l, mut fb_r) = (loop_to_f32(fb_l), loop_to_f32(fb_r));
+                    // Natural Damping: only the feedback goes through it, so the first echo is as bright as ever
+                    // and each one after that has been through it once more than the last. The more feedback,
+                    // the lower it sits, so long tails get dark rather than harsh
+                    if self.natural_damping {
+                        fb_l = self.damping_l.process(fb_l);
+                        fb_r = self.damping_r.process(fb_r);
+                    }
+                    if self.string_mode {
+                        self.string_state.0 += (fb_l - self.string_state.0) * string_coeff;
+                        self.string_state.1 += (fb_r - self.string_state.1) * string_coeff;
+                        (fb_l, fb_r) = self.string_state;
+                    }
+                    if self.bbd_on {
+                        fb_l = compand(fb_l);
+                        fb_r = compand(fb_r);
+                    }
+                    fb_l = self.saturation.shape(fb_l);
+                    fb_r = self.saturation.shape(fb_r);
+                    // Age's saturation, partway from clean to tanh
+                    if self.age > 0.0 {
+                        fb_l += (fb_l.tanh() - fb_l) * self.age;
+                        fb_r += (fb_r.tanh() - fb_r) * self.age;
+                    }
+                    // Post-Mix needs the limiter as well: Mix Lock and Stereo can turn the wet up, and the dry comes
+                    // straight back in on top, so nothing else stops the loop gain going past 1.
+                    // And String Mode, which is as close to 1 as Resonance and would pile up anything held
+                    if self.resonance > 0.0 || self.post_mix_feedback || self.string_mode {
+                        fb_l = limit_loop(fb_l);
+                        fb_r = limit_loop(fb_r);
+                    }
+                    if self.reverse_feedback {
+                        let (reversed_l, reversed_r) = self.reverser.process(fb_l, fb_r);
+                        fb_l = reversed_l;
+                        fb_r = reversed_r;
+                    }
+                    (LoopSample::from(fb_l), LoopSample::from(fb_r))
+                } else {
+                    (fb_l, fb_r)
+                };
+                // Cross-Linked: a bit of each side goes round on the other. Each side keeps the rest, so
+                // nothing gets louder: what's the same on both sides goes round as before, the rest dies faster
+                if self.channel_mode == ChannelMode::CrossLinked {
+                    let (own, other) = (LoopSample::from(1.0 - CROSS_LINK_AMOUNT), LoopSample::from(CROSS_LINK_AMOUNT));
+                    (fb_l, fb_r) = (fb_l * own + fb_r * other, fb_r * own + fb_l * other);
+                }
+                // Pan Rotate: every time round the loop, left and right get turned by the same angle, like a point
+                // on a circle. Something in the centre comes back a step to the right, then another step, and so on.
+                // A turn doesn't change the level, so the loop is just as stable. Past full right it carries on
+                // round the circle: the left comes back in upside down (out of phase), until it's all on the left,
+                // still upside down. The next half turn does the same the right way up, and then it's back where it started.
+                // In mid/side it turns mid and side instead, which also moves things across, just not as evenly
+                if self.pan_rotate > 0.0 {
+                    (fb_l, fb_r) = (fb_l * rotate_cos - fb_r * rotate_sin, fb_l * rotate_sin + fb_r * rotate_cos);
+                }
+                // last, so nothing after it can take the feedback back down towards denormal
+                if self.denormal_dither {
+                    fb_l += LoopSample::from(dither(&mut self.dither_state) * DENORMAL_DITHER_LEVEL);
+                    fb_r += LoopSample::from(dither(&mut self.dither_state) * DENORMAL_DITHER_LEVEL);
+                } else {
+                    if fb_l.abs() < LoopSample::from(DENORMAL_FLUSH_LEVEL) { fb_l = 0.0; }
+                    if fb_r.abs() < LoopSample::from(DENORMAL_FLUSH_LEVEL) { fb_r = 0.0; }
+                }
+
+                // Freeze: what goes back in is just what came out, with no input, no feedback gain and none of the
+                // colouring above, so the buffer goes round and round as it is forever. (The wet is still coloured
+                // on its way out, the same amount every time.) Network's two lines each go round on their own.
+                // "What came out" is read from the nearest whole sample, with no Motion and no interpolation:
+                // reading between samples is a gentle low-pass, and done on every pass it would dull the loop
+                // away. So a frozen loop is the delay rounded to a whole sample long, which nobody will hear.
+                // Switching straight over would click every time round: the newest sample in the buffer is followed
+                // by the oldest, and they have nothing to do with each other. So it fades over FREEZE_FADE_TIME from
+                // writing what would have gone in anyway to writing back what comes out. What would have gone in
+                // carries on from the newest sample, and by the end of the fade it's the start of the buffer
+                // coming round again, so that's a crossfade across the loop point. Unfreezing fades back the same way.
+                // A Clear (or changing the delay time) empties the buffer, so a freeze doesn't survive one
+                self.freeze_mix = if self.freeze {
+                    (self.freeze_mix + freeze_step).min(1.0)
+                } else {
+                    (self.freeze_mix - freeze_step).max(0.0)
+                };
+                if self.freeze_mix > 0.0 {
+                    let mix = LoopSample::from(self.freeze_mix);
+                    let held = delay.round();
+                    let (held_l, held_r) = self.delay_buffer.read((held, held), Interpolation::None);
+                    fb_l += (held_l - fb_l) * mix;
+                    fb_r += (held_r - fb_r) * mix;
+                    if self.network {
+                        let held = self.network_buffer.delay().round();
+                        let (held_l, held_r) = self.network_buffer.read((held, held), Interpolation::None);
+                        net_fb_l += (held_l - net_fb_l) * mix;
+                        net_fb_r += (held_r - net_fb_r) * mix;
+                    }
+                }
+
+                // add the feedback to the inputs, ready to come back out one delay from now
+                self.delay_buffer.write((loop_in_a + fb_l, loop_in_b + fb_r));
+                if self.network {
+                    self.network_buffer.write((loop_in_a + net_fb_l, loop_in_b + net_fb_r));
+                }
+
+                // everything from here on is left and right again
+                let (temp_l, temp_r) = if mid_side {
+                    from_mid_side(temp_a, temp_b)
+                } else {
+                    (temp_a, temp_b)
+                };
+
+                // fade the echo in if Attack is up. Done after feedback so only what we hear is smeared
+                let (smear_gain_l, smear_gain_r) = if self.true_stereo {
+                    (self.smear.process(temp_l, temp_l), self.smear_r.process(temp_r, temp_r))
+                } else {
+                    let gain = self.smear.process(temp_l, temp_r);
+                    (gain, gain)
+                };
+                let (temp_l, temp_r) = (temp_l * smear_gain_l, temp_r * smear_gain_r);
+
+                // the allpasses always run so they're already full of signal when Decorrelate is turned up
+                let (temp_l, temp_r) = (
+                    temp_l + (self.allpass_l.process(temp_l) - temp_l) * self.decorrelate,
+                    temp_r + (self.allpass_r.process(temp_r) - temp_r) * self.decorrelate,
+                );
+                // Phase Rotate only touches the right side, so the two sides drift apart in phase but not in level.
+                // Unlike Decorrelate there's no delay involved, so it's much subtler
+                let temp_r = if self.phase_rotate > 0.0 { self.phase_rotator.process(temp_r) } else { temp_r };
+                // Stereo: the offset makes a side out of a mono wet, then turning the side up makes it wider.
+                // Before Width so Mono Safe can still pull it back in if it goes too far
+                let temp_r = self.spread.process(temp_r, self.stereo);
+                let (temp_l, temp_r) = if self.stereo > 0.0 {
+                    let (mid, side) = to_mid_side(temp_l, temp_r);
+                    from_mid_side(mid, side * (1.0 + self.stereo * STEREO_MAX_WIDEN))
+                } else {
+                    (temp_l, temp_r)
+                };
+                // after decorrelation (and phase rotation), since that's the most likely thing to push the sides out of phase
+                let (temp_l, temp_r) = self.width.process(temp_l, temp_r, correlation_floor);
+
+                // Tremolo: the wet goes from full level down to 1 - depth and back up again, once per cycle.
+                // It's only on what we hear, the feedback is left alone
+                let tremolo_gain = if self.tremolo_depth > 0.0 {
+                    self.tremolo_phase = (self.tremolo_phase + tremolo_step).fract();
+                    1.0 - self.tremolo_depth * (1.0 - self.tremolo_shape.value(self.tremolo_phase)) * 0.5
+                } else {
+                    1.0
+                };
+                let (temp_l, temp_r) = (temp_l * tremolo_gain, temp_r * tremolo_gain);
+                // before ducking and clearing, which are meant to change the level
+                let (temp_l, temp_r) = if self.mix_lock && self.true_stereo {
+                    let gain_l = self.level_match.process((in_l_s, in_l_s), (temp_l, temp_l));
+                    let gain_r = self.level_match_r.process((in_r_s, in_r_s), (temp_r, temp_r));
+                    (temp_l * gain_l, temp_r * gain_r)
+                } else if self.mix_lock {
+                    let gain = self.level_match.process((in_l_s, in_r_s), (temp_l, temp_r));
+                    (temp_l * gain, temp_r * gain)
+                } else {
+                    (temp_l, temp_r)
+                };
+                let (temp_l, temp_r) = (temp_l * duck_gain_l * self.clear_gain, temp_r * duck_gain_r * self.clear_gain);
+                (temp_l, temp_r)
+            };
+            self.wet_meter.update(temp_l, temp_r);
+
+            // replace the output samples with a mix of what came out of the delay_buffer
+            // and the original value, depending on dry/wet percentage
+            // Possible expansion: Allow possibility to have unsynced left and right delays
+            // the dry comes from our copy of the input. Reading it from the output only worked
+            // for hosts that process in place, everywhere else the output starts out as garbage
+            let (dry_l, dry_r) = (in_l_s * self.dry_pan_gains.0, in_r_s * self.dry_pan_gains.1);
+            let mix = if self.mix_lock { mix_samples_locked } else { mix_samples };
+            *out_l_s = mix(dry_l, temp_l, dry_wet, self.invert_dry);
+            *out_r_s = mix(dry_r, temp_r, dry_wet, self.invert_dry);
+            self.last_mix = (*out_l_s, *out_r_s);
+            if self.duck_source == DuckSource::Dry {
+                self.last_dry = (mix(dry_l, 0.0, dry_wet, self.invert_dry), mix(dry_r, 0.0, dry_wet, self.invert_dry));
+            }
+            // both mixes are just the dry times one gain plus the wet times another, so mixing each with
+            // nothing on the other side gives the dry and wet on their own at the level they'd have in the mix
+            if self.output_routing != OutputRouting::Stereo {
+                let dry = mix((dry_l + dry_r) * 0.5, 0.0, dry_wet, self.invert_dry);
+                let wet = mix(0.0, (temp_l + temp_r) * 0.5, dry_wet, self.invert_dry);
+                (*out_l_s, *out_r_s) = match self.output_routing {
+                    OutputRouting::WetLeftDryRight => (wet, dry),
+                    _ => (dry, wet),
+                };
+            }
+
+            // while the stutter is going it takes over the output
+            let (stutter_l, stutter_r) = self.stutter.process((in_l_s, in_r_s), (*out_l_s, *out_r_s));
+            // the loop goes on top of all of it. It records the input (after the trims, like the dry), not the output,
+            // so overdubbing doesn't record the loop back into itself
+            let (stutter_l, stutter_r) = self.looper.process(
+                (in_l_s, in_r_s), (stutter_l, stutter_r), self.loop_overdub, self.loop_level,
+            );
+
+            // mono comes after everything that makes sound, so it really is the whole output
+            let (stutter_l, stutter_r) = match self.mono_output {
+                MonoOutput::Off => (stutter_l, stutter_r),
+                MonoOutput::Mono => {
+                    let mono = (stutter_l + stutter_r) * 0.5;
+                    (mono, mono)
+                },
+                MonoOutput::LoFi => {
+                    let mono = self.mono_filter.process((stutter_l + stutter_r) * 0.5);
+                    (mono, mono)
+                },
+            };
+            // Off by default so the output is untouched, but there if the low end needs cleaning up.
+            // The filters only run while it's on, so switching it on starts them from silence
+            let (stutter_l, stutter_r) = if self.subsonic {
+                (self.subsonic_l.process(stutter_l), self.subsonic_r.process(stutter_r))
+            } else {
+                (stutter_l, stutter_r)
+            };
+
+            // only ramps for the first START_FADE_TIME after new or resume, after that start_gain stays at 1
+            self.start_gain = (self.start_gain + self.start_step).min(1.0);
+            *out_l_s = stutter_l * self.output_gain * self.start_gain;
+            *out_r_s = stutter_r * self.output_gain * self.start_gain;
+
+            self.output_meter.update(*out_l_s, *out_r_s);
+            self.last_output = (*out_l_s, *out_r_s);
+        }
+
+        #[cfg(feature = "cpu-usage")]
+        self.cpu_meter.update(started.elapsed(), samples, self.sample_rate);
+    }
+
+    // Everything at its default, at `sample_rate`, with no host. default() and new() both start from this,
+    // so there's one place to set up each field. Every buffer is sized and every filter tuned, so it works
+    // as it is without waiting for set_sample_rate. No scratch space yet, process makes it if it has to
+    fn at_sample_rate(sample_rate: f32) -> SillyDelay {
+        // from the knobs' defaults, so a host sending the default back doesn't count as a change
+        let (delay_time, delay_time_2) = (knob_to_delay(Param::DelayTime.default()), knob_to_delay(Param::DelayTime2.default()));
+        let mut delay = SillyDelay {
+            delay_buffer: reload_delay_buffer(sample_rate, delay_time),
+            delay_time,
+            delay_knob: Param::DelayTime.default(),
+            dry_wet: 1.0,
+            sample_rate,
+            feedback_amt: 0.1,
+            invert_dry: false,
+            attack: 0.0,
+            smear: Smear::new(sample_rate, 0.0),
+            decorrelate: 0.0,
+            allpass_l: Allpass::new(sample_rate, DECORRELATE_TIME_L),
+            allpass_r: Allpass::new(sample_rate, DECORRELATE_TIME_R),
+            mid_side: false,
+            buffer_mid_side: false,
+            stutter_trigger: false,
+            stutter_length: 0.25,
+            stutter_repeats: 0.2,
+            stutter: Stutter::new(sample_rate),
+            loop_trigger: false,
+            loop_length: 0.2,
+            loop_overdub: false,
+            loop_level: 1.0,
+            looper: Looper::new(sample_rate),
+            duck_depth: 0.0,
+            ducker: Ducker::new(sample_rate),
+            duck_source: DuckSource::Input,
+            last_output: (0.0, 0.0),
+            last_dry: (0.0, 0.0),
+            post_mix_feedback: false,
+            freeze: false,
+            freeze_mix: 0.0,
+            last_mix: (0.0, 0.0),
+            lookahead_time: 0.0,
+            lookahead_buffer: reload_lookahead_buffer(sample_rate, 0.0),
+            input_routing: InputRouting::Normal,
+            clear_trigger: false,
+            pending_buffers: None,
+            clear_gain: 1.0,
+            clear_step: 1.0 / (CLEAR_FADE_TIME * sample_rate).max(1.0),
+            start_gain: 0.0,
+            start_step: 1.0 / (START_FADE_TIME * sample_rate).max(1.0),
+            slapback: false,
+            bbd_on: false,
+            bbd: Bbd::new(sample_rate, delay_time),
+            bits: 0.0,
+            downsample: 0.0,
+            crusher: Crusher::new(),
+            mono_safe: false,
+            correlation_floor: 0.5,
+            width: Width::new(sample_rate),
+            phase_rotate: 0.0,
+            phase_rotator: Biquad::default(),
+            repeat_limit: 1.0,
+            since_input: 0,
+            input_trim: 0.5,
+            input_gain: 1.0,
+            output_trim: 0.5,
+            output_gain: 1.0,
+            reverse_feedback: false,
+            dry_pan: 0.5,
+            dry_pan_gains: (1.0, 1.0),
+            tremolo_rate: 0.5,
+            tremolo_depth: 0.0,
+            tremolo_shape: TremoloShape::Sine,
+            tremolo_phase: 0.0,
+            mix_lock: false,
+            saturation: Saturation::Off,
+            true_stereo: false,
+            smear_r: Smear::new(sample_rate, 0.0),
+            ducker_r: Ducker::new(sample_rate),
+            since_input_r: 0,
+            level_match_r: LevelMatch::new(sample_rate),
+            mono_output: MonoOutput::Off,
+            output_routing: OutputRouting::Stereo,
+            channel_mode: ChannelMode::DualMono,
+            side_only: false,
+            snapshots: [None, None],
+            morph: 0.0,
+            resonance: 0.0,
+            test_signal: TestSignal::Off,
+            test_phase: 0.0,
+            test_countdown: 0,
+            quantize: 0.0,
+            subsonic: false,
+            subsonic_l: subsonic_filter(sample_rate),
+            subsonic_r: subsonic_filter(sample_rate),
+            stereo: 0.0,
+            spread: Spread::new(sample_rate),
+            motion: 0.0,
+            denormal_dither: false,
+            pan_rotate: 0.0,
+            interpolation: Interpolation::Linear,
+            natural_damping: false,
+            damping_l: Biquad::default(),
+            damping_r: Biquad::default(),
+            age: 0.0,
+            age_filter_l: Biquad::default(),
+            age_filter_r: Biquad::default(),
+            age_noise_state: AGE_NOISE_SEED,
+            idle_when_dry: false,
+            short_delay_guard: false,
+            note_delay: false,
+            held_notes: HeldNotes::default(),
+            delay_in_hz: false,
+            string_mode: false,
+            string_damping: Param::StringDamping.default(),
+            string_state: (0.0, 0.0),
+            dither_state: DITHER_SEED,
+            drift_l: Drift::new(sample_rate, DRIFT_SEED_L),
+            drift_r: Drift::new(sample_rate, DRIFT_SEED_R),
+            scatter: 0.0,
+            scatter_seed: 0.0,
+            scatterer: Scatter::new(sample_rate, scatter_seed(0.0)),
+            mono_filter: mono_lofi_filter(sample_rate),
+            level_match: LevelMatch::new(sample_rate),
+            reverser: Reverser::new(sample_rate, delay_time),
+            network: false,
+            delay_time_2,
+            delay_knob_2: Param::DelayTime2.default(),
+            network_buffer: reload_delay_buffer(sample_rate, delay_time_2),
+            block_size: 0,
+            scratch_l: Vec::new(),
+            scratch_r: Vec::new(),
+            sidechain_l: Vec::new(),
+            sidechain_r: Vec::new(),
+            has_sidechain: false,
+            input_meter: PeakMeter::new(sample_rate),
+            wet_meter: PeakMeter::new(sample_rate),
+            output_meter: PeakMeter::new(sample_rate),
+            #[cfg(feature = "diagnostics")]
+            diagnostics: Diagnostics::default(),
+            #[cfg(feature = "cpu-usage")]
+            cpu_meter: CpuMeter::default(),
+            parameter_listener: None,
+            host: None,
+        };
+        delay.retune_filters();
+        delay
+    }
+}
+
+impl Default for SillyDelay {
+
+    // This is somehow necessary, but doesn't really do much since we initialize later anyway.
+    // Not every host (or test) does though, so this has to be a plugin that works as it is, at 44.1k
+    fn default() -> SillyDelay {
+        SillyDelay::at_sample_rate(44100.)
+    }
+}
+
+// implement Plugin for SillyDelay
+impl Plugin for SillyDelay {
+
+    // initialize
+    fn new(host: HostCallback) -> Self {
+
+        // Get the sample rate immediately before anything else
+        // In order to set the sample rate in the case that it's not changed
+        // use get_time_info with no flags. Sample rate is always valid in TimeInfo
+        // Possible improvement: set Tempo flag and use Tempo with sample rate for Synced delay times.
+        // Not every host knows its sample rate this early though, so if it gives nothing (or nonsense)
+        // start at 44.1k like default() does. The real rate turns up in set_sample_rate before any audio.
+        let sample_rate = match host.get_time_info(0) {
+            Some(TimeInfo { sample_rate, .. }) if sample_rate.is_finite() && sample_rate > 0.0 => sample_rate as f32,
+            _ => 44100.,
+        };
+
+        // the host may already know how big its blocks will be. If it doesn't it returns 0
+        // and we'll find out in set_block_size or process instead.
+        let block_size = host.get_block_size().max(0) as usize;
+
+        // the same goes for this as for default(): if set_sample_rate never comes it has to work as it is
+        let mut delay = SillyDelay::at_sample_rate(sample_rate);
+        delay.block_size = block_size;
+        delay.resize_scratch(block_size);
+        delay.host = Some(host);
+        delay
+    }
+
+    // necessary for Plugin trait
+    fn get_info(&self) -> Info {
+        Info { 
+            parameters: Param::ALL.len() as i32,
+            // the 3rd and 4th are a sidechain, only used for ducking (see DuckSource)
+            inputs: 4,
+            outputs: 2,
+            // for the CCs in MIDI_CC_MAP
+            midi_inputs: 1,
+            category: Category::Effect,
+            f64_precision: false,
+
+            name: "SillyDelay".to_string(),
+            vendor: "Volfym".to_string(),
+            // randomly generated online. necessary to work
+            unique_id: 486893,
+            // kept the same as PARAMETER_VERSION, so hosts that look can tell older sessions' values meant something else
+            version: PARAMETER_VERSION,
+            // the lookahead makes everything (dry included) come out late by this many samples
+            initial_delay: self.lookahead_buffer.capacity() as i32,
+
+            ..Info::default()
+        }
+    }
+
+    // sets parameters when host changes them.
+    fn set_parameter(&mut self, index: i32, value: f32) {
+        let Some(param) = Param::from_i32(index) else { return; };
+        match param {
+            // delay time. delay_buffer is also reloaded. Because of this it's not possible to have a smooth change
+            // between one delay time and another, but at least the old echoes fade out instead of cutting off
+            // (see clear_buffer). To prevent any issues when loading delay_buffer
+            // delay time cannot be zero. It also can't go above 1.0 even if the host sends something silly,
+            // since that decides how big delay_buffer gets. clamp lets NaN through, so that's caught first
+            Param::DelayTime => {
+                let before = self.current_delay_time();
+                self.delay_knob = if value.is_nan() { 0.0 } else { value.clamp(0.0, 1.0) };
+                self.delay_time = knob_to_delay(self.delay_knob);
+                // slapback has its own delay time, so the buffer (and the BBD's tone) can stay as it is.
+                // Same if Delay Quantize snapped it to where it already was
+                if self.current_delay_time() != before {
+                    self.clear_buffer();
+                }
+            },
+            // I don't want any problems below MIN_FEEDBACK, so minimum cap of feedback is 10%
+            // although in reality that is equivalent to 0 feedback.
+            Param::Feedback => {
+                self.feedback_amt = value.max(param.range().0);
+                self.retune_damping();
+            },
+            // a tail left in the buffer when it stopped would come straight back out as the wet comes up again,
+            // however long ago that was. Clearing fades it out, but at 0% there's nothing to hear anyway
+            Param::DryWet => {
+                let was_idle = self.idle();
+                self.dry_wet = value;
+                if self.idle() && !was_idle {
+                    self.clear_buffer();
+                }
+            },
+            // a switch, so anything past halfway counts as on
+            Param::DryPolarity => self.invert_dry = value >= 0.5,
+            Param::Attack => {
+                self.attack = value;
+                let sample_rate = self.sample_rate;
+                self.smear.set_attack(sample_rate, value);
+                self.smear_r.set_attack(sample_rate, value);
+            },
+            Param::Decorrelate => self.decorrelate = value,
+            Param::StereoMode => self.change_mid_side(|delay| delay.mid_side = value >= 0.5),
+            // momentary, so it only does anything when it goes from off to on
+            Param::Stutter => {
+                let trigger = value >= 0.5;
+                if trigger && !self.stutter_trigger {
+                    let slice_len = self.stutter_time() * self.sample_rate;
+                    self.stutter.trigger(slice_len as usize, self.stutter_repeat_count());
+                }
+                self.stutter_trigger = trigger;
+            },
+            Param::StutterLength => self.stutter_length = value,
+            // momentary, like Stutter. The length is whatever Loop Length is at the time
+            Param::LoopRecord => {
+                let trigger = value >= 0.5;
+                if trigger && !self.loop_trigger {
+                    let len = self.loop_time() * self.sample_rate;
+                    self.looper.trigger(len as usize);
+                }
+                self.loop_trigger = trigger;
+            },
+            Param::LoopLength => self.loop_length = value,
+            Param::LoopOverdub => self.loop_overdub = value >= 0.5,
+            Param::LoopLevel => self.loop_level = value,
+            Param::StutterRepeats => self.stutter_repeats = value,
+            Param::DuckDepth => self.duck_depth = value,
+            // hosts generally only check the latency (initial_delay) when the plugin is loaded or turned on,
+            // so changing this while playing can leave things out of time until then
+            // A new buffer throws away the audio waiting in this one, which clicks. So only when the length
+            // actually changes, not every time a host sends the same value again
+            Param::Lookahead => {
+                self.lookahead_time = value;
+                if lookahead_samples(self.sample_rate, value) != self.lookahead_buffer.capacity() {
+                    self.lookahead_buffer = reload_lookahead_buffer(self.sample_rate, self.lookahead_time);
+                }
+            },
+            Param::InputRouting => self.input_routing = InputRouting::from_param(value),
+            // momentary, like Stutter
+            Param::Clear => {
+                let trigger = value >= 0.5;
+                if trigger && !self.clear_trigger {
+                    self.clear_buffer();
+                }
+                self.clear_trigger = trigger;
+            },
+            // the delay time changes both ways, so the buffer does too
+            Param::Slapback if self.slapback != (value >= 0.5) => {
+                self.slapback = value >= 0.5;
+                self.clear_buffer();
+            },
+            Param::Slapback => (),
+            Param::Bbd => self.bbd_on = value >= 0.5,
+            Param::Bits => self.bits = value,
+            Param::Downsample => self.downsample = value,
+            Param::Network => self.network = value >= 0.5,
+            Param::MonoSafe => self.mono_safe = value >= 0.5,
+            Param::CorrelationFloor => self.correlation_floor = value,
+            Param::RepeatLimit => self.repeat_limit = value,
+            Param::ReverseFeedback => self.reverse_feedback = value >= 0.5,
+            Param::TremoloRate => self.tremolo_rate = value,
+            Param::MixLock => self.mix_lock = value >= 0.5,
+            Param::Saturation => self.saturation = Saturation::from_param(value),
+            Param::TrueStereo => self.true_stereo = value >= 0.5,
+            Param::MonoOutput => self.mono_output = MonoOutput::from_param(value),
+            Param::OutputRouting => self.output_routing = OutputRouting::from_param(value),
+            Param::Freeze => self.freeze = value >= 0.5,
+            // going in or out of mid/side means the buffer's full of the wrong thing, same as Stereo Mode
+            Param::ChannelMode => self.change_mid_side(|delay| delay.channel_mode = ChannelMode::from_param(value)),
+            Param::DuckSource => self.duck_source = DuckSource::from_param(value),
+            Param::Morph => {
+                self.morph = value;
+                self.apply_morph();
+            },
+            // same as Channel Mode
+            Param::SideOnly => self.change_mid_side(|delay| delay.side_only = value >= 0.5),
+            Param::Resonance => self.resonance = value,
+            Param::Stereo => self.stereo = value,
+            Param::Motion => self.motion = value,
+            Param::Scatter => self.scatter = value,
+            // starts over from the new seed, so the same seed always scatters the same way from here on
+            Param::ScatterSeed => {
+                let before = scatter_seed_number(self.scatter_seed);
+                self.scatter_seed = value;
+                if scatter_seed_number(value) != before {
+                    self.scatterer = Scatter::new(self.sample_rate, scatter_seed(value));
+                }
+            },
+            Param::Denormals => self.denormal_dither = value >= 0.5,
+            Param::PanRotate => self.pan_rotate = value,
+            // only retune if it changed, like Phase Rotate
+            Param::Age => {
+                if value != self.age {
+                    self.age = value;
+                    self.retune_age_filters();
+                }
+            },
+            Param::Interpolation => self.interpolation = Interpolation::from_param(value),
+            Param::ShortDelayGuard => self.short_delay_guard = value >= 0.5,
+            Param::FeedbackTap => self.post_mix_feedback = value >= 0.5,
+            // starts the low-pass from silence, so nothing left over from the last time goes round
+            Param::StringMode => {
+                if value >= 0.5 && !self.string_mode {
+                    self.string_state = (0.0, 0.0);
+                }
+                self.string_mode = value >= 0.5;
+            },
+            Param::StringDamping => self.string_damping = value,
+            // the knob means something else now, so like Note Delay it clears if that moved the delay
+            Param::DelayInHz => {
+                let before = self.current_delay_time();
+                self.delay_in_hz = value >= 0.5;
+                if self.current_delay_time() != before {
+                    self.clear_buffer();
+                }
+            },
+            // whatever notes are already held take over straight away
+            Param::NoteDelay => {
+                let before = self.current_delay_time();
+                self.note_delay = value >= 0.5;
+                if self.current_delay_time() != before {
+                    self.clear_buffer();
+                }
+            },
+            // same as Dry/Wet
+            Param::IdleWhenDry => {
+                let was_idle = self.idle();
+                self.idle_when_dry = value >= 0.5;
+                if self.idle() && !was_idle {
+                    self.clear_buffer();
+                }
+            },
+            // same as Subsonic, it starts again empty
+            Param::NaturalDamping => {
+                let natural_damping = value >= 0.5;
+                if natural_damping && !self.natural_damping {
+                    self.damping_l = Biquad::default();
+                    self.damping_r = Biquad::default();
+                    self.retune_damping();
+                }
+                self.natural_damping = natural_damping;
+            },
+            Param::Subsonic => {
+                let subsonic = value >= 0.5;
+                // whatever was left in the filters from last time it was on is stale now
+                if subsonic && !self.subsonic {
+                    self.subsonic_l = subsonic_filter(self.sample_rate);
+                    self.subsonic_r = subsonic_filter(self.sample_rate);
+                }
+                self.subsonic = subsonic;
+            }
+            Param::TestSignal => self.test_signal = TestSignal::from_param(value),
+            // only start over if the delay time has actually moved, since that throws away the echoes
+            Param::Quantize => {
+                let before = self.current_delay_time();
+                self.quantize = value;
+                if self.current_delay_time() != before {
+                    self.clear_buffer();
+                }
+            },
+            Param::TremoloDepth => self.tremolo_depth = value,
+            Param::TremoloShape => self.tremolo_shape = TremoloShape::from_param(value),
+            Param::DryPan => {
+                self.dry_pan = value;
+                self.dry_pan_gains = pan_law(value);
+            },
+            Param::InputTrim => {
+                self.input_trim = value;
+                self.input_gain = db_to_gain(trim_db(value));
+            },
+            Param::OutputTrim => {
+                self.output_trim = value;
+                self.output_gain = db_to_gain(trim_db(value));
+            },
+            // Hosts can send the same value every block while automating something else, and Morph sends it every
+            // time it moves. Working out the coefficients takes a pow, a sin and a cos, so only do it if it changed.
+            // set_sample_rate always retunes, so the coefficients are never stale
+            Param::PhaseRotate => {
+                if value != self.phase_rotate {
+                    self.phase_rotate = value;
+                    self.retune_phase_rotator();
+                }
+            },
+            // same as Delay Time, only if it actually moved. The second line is only heard with Network on,
+            // so otherwise there's nothing to fade and the new one goes straight in. Unless a clear is already
+            // on its way, since that would swap the old length back in when it's done
+            Param::DelayTime2 => {
+                let before = self.delay_time_2;
+                self.delay_knob_2 = if value.is_nan() { 0.0 } else { value.clamp(0.0, 1.0) };
+                self.delay_time_2 = knob_to_delay(self.delay_knob_2);
+                if self.delay_time_2 != before {
+                    if self.network || self.pending_buffers.is_some() {
+                        self.clear_buffer();
+                    } else {
+                        self.network_buffer = reload_delay_buffer(self.sample_rate, self.delay_time_2);
+                    }
+                }
+            },
+        }
+
+        let kept = self.get_parameter(index);
+        if let Some(listener) = &mut self.parameter_listener {
+            listener(param, kept);
+        }
+    }
+
+    // provides params when host asks for them.
+    fn get_parameter(&self, index: i32) -> f32 {
+       let Some(param) = Param::from_i32(index) else { return 0.0 };
+       match param {
+           Param::DelayTime => self.delay_knob,
+           Param::Feedback => self.feedback_amt,
+           Param::DryWet => self.dry_wet,
+           Param::DryPolarity => switch_value(self.invert_dry),
+           Param::Attack => self.attack,
+           Param::Decorrelate => self.decorrelate,
+           Param::StereoMode => switch_value(self.mid_side),
+           Param::Stutter => switch_value(self.stutter_trigger),
+           Param::StutterLength => self.stutter_length,
+           Param::LoopRecord => switch_value(self.loop_trigger),
+           Param::LoopLength => self.loop_length,
+           Param::LoopOverdub => switch_value(self.loop_overdub),
+           Param::LoopLevel => self.loop_level,
+           Param::StutterRepeats => self.stutter_repeats,
+           Param::DuckDepth => self.duck_depth,
+           Param::Lookahead => self.lookahead_time,
+           Param::InputRouting => self.input_routing.to_param(),
+           Param::Clear => switch_value(self.clear_trigger),
+           Param::Slapback => switch_value(self.slapback),
+           Param::Bbd => switch_value(self.bbd_on),
+           Param::Bits => self.bits,
+           Param::Downsample => self.downsample,
+           Param::Network => switch_value(self.network),
+           Param::DelayTime2 => self.delay_knob_2,
+           Param::MonoSafe => switch_value(self.mono_safe),
+           Param::CorrelationFloor => self.correlation_floor,
+           Param::PhaseRotate => self.phase_rotate,
+           Param::RepeatLimit => self.repeat_limit,
+           Param::InputTrim => self.input_trim,
+           Param::OutputTrim => self.output_trim,
+           Param::ReverseFeedback => switch_value(self.reverse_feedback),
+           Param::DryPan => self.dry_pan,
+           Param::TremoloRate => self.tremolo_rate,
+           Param::MixLock => switch_value(self.mix_lock),
+           Param::Saturation => self.saturation.to_param(),
+           Param::TrueStereo => switch_value(self.true_stereo),
+           Param::MonoOutput => self.mono_output.to_param(),
+           Param::OutputRouting => self.output_routing.to_param(),
+           Param::Freeze => switch_value(self.freeze),
+           Param::ChannelMode => self.channel_mode.to_param(),
+           Param::SideOnly => switch_value(self.side_only),
+           Param::Morph => self.morph,
+           Param::DuckSource => self.duck_source.to_param(),
+           Param::Resonance => self.resonance,
+           Param::TestSignal => self.test_signal.to_param(),
+           Param::Quantize => self.quantize,
+           Param::Subsonic => switch_value(self.subsonic),
+           Param::Stereo => self.stereo,
+           Param::Motion => self.motion,
+           Param::Scatter => self.scatter,
+           Param::ScatterSeed => self.scatter_seed,
+           Param::Age => self.age,
+           Param::IdleWhenDry => switch_value(self.idle_when_dry),
+           Param::ShortDelayGuard => switch_value(self.short_delay_guard),
+           Param::FeedbackTap => switch_value(self.post_mix_feedback),
+           Param::NoteDelay => switch_value(self.note_delay),
+           Param::DelayInHz => switch_value(self.delay_in_hz),
+           Param::StringMode => switch_value(self.string_mode),
+           Param::StringDamping => self.string_damping,
+           Param::Denormals => switch_value(self.denormal_dither),
+           Param::PanRotate => self.pan_rotate,
+           Param::Interpolation => self.interpolation.to_param(),
+           Param::NaturalDamping => switch_value(self.natural_damping),
+           Param::TremoloDepth => self.tremolo_depth,
+           Param::TremoloShape => self.tremolo_shape.to_param(),
+       }
+    }
+
+    // provides param names when host asks for them.
+    fn get_parameter_name(&self, index: i32) -> String {
+        Param::from_i32(index).map_or("", Param::name).to_string()
+    }
+
+    // param value text.
+    fn get_parameter_text(&self, index: i32) -> String {
+        let Some(param) = Param::from_i32(index) else { return "".to_string() };
+        match param {
+            // all params go from 0 to 1. Delay time is multiplied by two later
+            // because I wanted a longer delay time. 
+            // what's actually in use, ie. after Delay Quantize (but ignoring slapback, which has its own time)
+            // or in Hz mode the pitch, and the nearest note to it
+            Param::DelayTime if self.delay_in_hz => {
+                let freq = knob_to_hz(self.delay_knob);
+                format!("{:.1} ({})", freq, note_name(freq))
+            },
+            Param::DelayTime => format!("{:.1}", self.quantized_delay_time() * 2000.0),
+            Param::Feedback => format!("{}", self.feedback_amt * 100.0),
+            Param::DryWet => format!("{}", self.dry_wet * 100.0),
+            Param::DryPolarity => if self.invert_dry { "Inverted".to_string() } else { "Normal".to_string() },
+            Param::Attack => format!("{}", self.attack * MAX_ATTACK_TIME * 1000.0),
+            Param::Decorrelate => format!("{}", self.decorrelate * 100.0),
+            Param::StereoMode => if self.mid_side { "Mid/Side".to_string() } else { "Left/Right".to_string() },
+            Param::Stutter => on_off(self.stutter_trigger),
+            Param::StutterLength => format!("{}", self.stutter_time() * 1000.0),
+            Param::LoopRecord => on_off(self.loop_trigger),
+            Param::LoopLength => format!("{:.2}", self.loop_time()),
+            Param::LoopOverdub => on_off(self.loop_overdub),
+            Param::LoopLevel => format!("{}", self.loop_level * 100.0),
+            Param::StutterRepeats => format!("{}", self.stutter_repeat_count()),
+            Param::DuckDepth => format!("{}", self.duck_depth * 100.0),
+            Param::Lookahead => format!("{}", self.lookahead_time * MAX_LOOKAHEAD_TIME * 1000.0),
+            Param::InputRouting => match self.input_routing {
+                InputRouting::Normal => "Normal".to_string(),
+                InputRouting::Swap => "Swap L/R".to_string(),
+                InputRouting::Mono => "Mono".to_string(),
+            },
+            Param::Clear => on_off(self.clear_trigger),
+            Param::Slapback => on_off(self.slapback),
+            Param::Bbd => on_off(self.bbd_on),
+            Param::Bits => if self.bits == 0.0 { "Off".to_string() } else { format!("{}", self.crush_bits()) },
+            Param::Downsample => format!("{}", self.downsample_factor()),
+            Param::Network => on_off(self.network),
+            Param::DelayTime2 => format!("{:.1}", self.delay_time_2 * 2000.0),
+            Param::MonoSafe => on_off(self.mono_safe),
+            Param::CorrelationFloor => format!("{:.2}", self.correlation_floor * 2.0 - 1.0),
+            Param::RepeatLimit => match self.repeat_count() {
+                Some(count) => format!("{}", count),
+                None => "Off".to_string(),
+            },
+            Param::InputTrim => format!("{:.1}", trim_db(self.input_trim)),
+            Param::OutputTrim => format!("{:.1}", trim_db(self.output_trim)),
+            Param::ReverseFeedback => on_off(self.reverse_feedback),
+            Param::TremoloRate => format!("{:.2}", self.tremolo_rate_hz()),
+            Param::MixLock => on_off(self.mix_lock),
+            Param::TrueStereo => on_off(self.true_stereo),
+            Param::Resonance => format!("{}", self.resonance * 100.0),
+            Param::Stereo => format!("{}", self.stereo * 100.0),
+            Param::Motion => format!("{}", self.motion * 100.0),
+            Param::Scatter => format!("{}", self.scatter * 100.0),
+            Param::ScatterSeed => format!("{}", scatter_seed_number(self.scatter_seed)),
+            Param::Age => format!("{}", self.age * 100.0),
+            Param::IdleWhenDry => on_off(self.idle_when_dry),
+            Param::ShortDelayGuard => on_off(self.short_delay_guard),
+            Param::FeedbackTap => if self.post_mix_feedback { "Post-Mix".to_string() } else { "Pre-Mix".to_string() },
+            Param::NoteDelay => on_off(self.note_delay),
+            Param::DelayInHz => on_off(self.delay_in_hz),
+            Param::StringMode => on_off(self.string_mode),
+            Param::StringDamping => format!("{}", self.string_damping * 100.0),
+            Param::Interpolation => match self.interpolation {
+                Interpolation::None => "None".to_string(),
+                Interpolation::Linear => "Linear".to_string(),
+                Interpolation::Cubic => "Cubic".to_string(),
+            },
+            Param::PanRotate => format!("{:.0}", self.pan_rotate * PAN_ROTATE_MAX_STEP.to_degrees()),
+            Param::Denormals => if self.denormal_dither { "Dither".to_string() } else { "Flush".to_string() },
+            Param::Subsonic => on_off(self.subsonic),
+            Param::NaturalDamping => on_off(self.natural_damping),
+            Param::Quantize => if self.quantize_grid() == 0.0 { "Off".to_string() } else { format!("{}", self.quantize_grid()) },
+            Param::TestSignal => match self.test_signal {
+                TestSignal::Off => "Off".to_string(),
+                TestSignal::Tone => "Tone".to_string(),
+                TestSignal::Impulse => "Impulse".to_string(),
+            },
+            Param::DuckSource => match self.duck_source {
+                DuckSource::Input => "Input".to_string(),
+                DuckSource::Dry => "Dry".to_string(),
+                DuckSource::Output => "Output".to_string(),
+                DuckSource::Sidechain => "Sidechain".to_string(),
+            },
+            Param::Morph => format!("{}", self.morph * 100.0),
+            Param::SideOnly => on_off(self.side_only),
+            Param::ChannelMode => match self.channel_mode {
+                ChannelMode::DualMono => "Dual Mono".to_string(),
+                ChannelMode::CrossLinked => "Cross-Linked".to_string(),
+                ChannelMode::MidSide => "Mid/Side".to_string(),
+            },
+            Param::MonoOutput => match self.mono_output {
+                MonoOutput::Off => "Off".to_string(),
+                MonoOutput::Mono => "Mono".to_string(),
+                MonoOutput::LoFi => "Mono Lo-Fi".to_string(),
+            },
+            Param::OutputRouting => match self.output_routing {
+                OutputRouting::Stereo => "Stereo".to_string(),
+                OutputRouting::DryLeftWetRight => "Dry L / Wet R".to_string(),
+                OutputRouting::WetLeftDryRight => "Wet L / Dry R".to_string(),
+            },
+            Param::Freeze => on_off(self.freeze),
+            Param::Saturation => match self.saturation {
+                Saturation::Off => "Off".to_string(),
+                Saturation::Soft => "Soft".to_string(),
+                Saturation::Hard => "Hard".to_string(),
+                Saturation::Fold => "Fold".to_string(),
+                Saturation::Diode => "Diode".to_string(),
+            },
+            Param::TremoloDepth => format!("{}", self.tremolo_depth * 100.0),
+            Param::TremoloShape => match self.tremolo_shape {
+                TremoloShape::Sine => "Sine".to_string(),
+                TremoloShape::Triangle => "Triangle".to_string(),
+                TremoloShape::Square => "Square".to_string(),
+            },
+            Param::DryPan => {
+                let amount = ((self.dry_pan * 2.0 - 1.0) * 100.0).round();
+                if amount < 0.0 {
+                    format!("L{}", -amount)
+                } else if amount > 0.0 {
+                    format!("R{}", amount)
+                } else {
+                    "C".to_string()
+                }
+            },
+            Param::PhaseRotate => if self.phase_rotate == 0.0 { "Off".to_string() } else { format!("{:.0}", self.phase_rotate_freq()) },
+        }
+    }
+
+    // param labels. 
+    fn get_parameter_label(&self, index: i32) -> String {
+        match Param::from_i32(index) {
+            Some(Param::DelayTime) if self.delay_in_hz => "Hz".to_string(),
+            param => param.map_or("", Param::label).to_string(),
+        }
+    }
+
+    // in the case that the host changes sample rate
+    // this function is called. We update the sample_rate held in SillyDelay
+    // and also reload the delay_buffer to reflect the new sample_rate
+    // Some hosts call this early with 0 (or before they really know). A rate like that would give
+    // empty buffers, so it's ignored and we keep whatever rate we had until a real one comes along.
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        if !sample_rate.is_finite() || sample_rate <= 0.0 {
+            self.report(Diagnostic::BadSampleRate(sample_rate));
+            return;
+        }
+        self.report(Diagnostic::BuffersRebuilt);
+        self.sample_rate = sample_rate;
+        self.delay_buffer = reload_delay_buffer(sample_rate, self.current_delay_time());
+        self.network_buffer = reload_delay_buffer(sample_rate, self.delay_time_2);
+        self.bbd = Bbd::new(sample_rate, self.current_delay_time());
+        self.reverser = Reverser::new(sample_rate, self.current_delay_time());
+        // the buffers were just replaced anyway, so there's nothing left to fade out
+        self.pending_buffers = None;
+        self.buffer_mid_side = self.mid_side_on();
+        self.clear_gain = 1.0;
+        self.clear_step = 1.0 / (CLEAR_FADE_TIME * sample_rate).max(1.0);
+        self.start_step = 1.0 / (START_FADE_TIME * sample_rate).max(1.0);
+        self.smear = Smear::new(sample_rate, self.attack);
+        self.smear_r = Smear::new(sample_rate, self.attack);
+        self.allpass_l = Allpass::new(sample_rate, DECORRELATE_TIME_L);
+        self.allpass_r = Allpass::new(sample_rate, DECORRELATE_TIME_R);
+        self.stutter = Stutter::new(sample_rate);
+        self.looper = Looper::new(sample_rate);
+        self.ducker = Ducker::new(sample_rate);
+        self.ducker_r = Ducker::new(sample_rate);
+        self.last_output = (0.0, 0.0);
+        self.last_dry = (0.0, 0.0);
+        self.last_mix = (0.0, 0.0);
+        self.freeze_mix = 0.0;
+        self.string_state = (0.0, 0.0);
+        self.width = Width::new(sample_rate);
+        self.level_match = LevelMatch::new(sample_rate);
+        self.level_match_r = LevelMatch::new(sample_rate);
+        self.mono_filter = mono_lofi_filter(sample_rate);
+        self.subsonic_l = subsonic_filter(sample_rate);
+        self.subsonic_r = subsonic_filter(sample_rate);
+        self.spread = Spread::new(sample_rate);
+        self.drift_l = Drift::new(sample_rate, DRIFT_SEED_L);
+        self.drift_r = Drift::new(sample_rate, DRIFT_SEED_R);
+        self.scatterer = Scatter::new(sample_rate, scatter_seed(self.scatter_seed));
+        self.phase_rotator = Biquad::default();
+        self.retune_filters();
+        self.lookahead_buffer = reload_lookahead_buffer(sample_rate, self.lookahead_time);
+        self.reset_meters();
+    }
+
+    // the host tells us the largest block it will send, so make room for it now rather than in process
+    fn set_block_size(&mut self, size: i64) {
+        self.block_size = size.max(0) as usize;
+        let block_size = self.block_size;
+        self.resize_scratch(block_size);
+    }
+
+    // the host has stopped processing, so whatever the meters are showing is stale
+    fn suspend(&mut self) {
+        self.reset_meters();
+    }
+
+    // Whatever was left in the buffers (or a preset with lots of feedback landing on the first loud block)
+    // can start with a jump, which clicks. So the output fades in over START_FADE_TIME, just like after new
+    fn resume(&mut self) {
+        self.start_gain = 0.0;
+    }
+
+    // MIDI only gets as far as setting parameters, so it all takes effect from the start of the next block
+    fn process_events(&mut self, events: &Events) {
+        for event in events.events() {
+            if let Event::Midi(midi) = event {
+                self.midi_event(midi.data);
+            }
+        }
+    }
+
+    // the host has to know we want MIDI, or it won't send any
+    fn can_do(&self, can_do: CanDo) -> Supported {
+        match can_do {
+            CanDo::ReceiveEvents | CanDo::ReceiveMidiEvent => Supported::Yes,
+            _ => Supported::Maybe,
+        }
+    }
+
+    // main processing goes here
+    fn process(&mut self, buffer: &mut AudioBuffer<f32>) {
+        let samples = buffer.samples();
+
+        // see resize_scratch. Normally this never happens.
+        if samples > self.scratch_l.len() {
+            self.report(Diagnostic::ScratchGrown(samples));
+            self.resize_scratch(samples);
+        }
+
+        // split the audio buffer into inputs and outputs, then copy the input channels into
+        // the scratch buffers so that everything below works on our own copy of the block.
+        // Some hosts process in place, ie. the input and output channels are the same memory, so
+        // as soon as an output sample is written the input sample is gone. Taking a copy first means
+        // nothing below ever reads from the host's input (or output) buffers after writing to them.
+        // Anything new that needs the input should read it from scratch_l/scratch_r, never from inputs.
+        let (inputs, outputs) = buffer.split();
+
+        // We ask for 4 in (2 plus the sidechain) and 2 out, but some hosts give a mono track 1 input anyway.
+        // One input gets used for both sides, no input at all is silence.
+        match inputs.len() {
+            0 => {
+                self.scratch_l[..samples].iter_mut().for_each(|sample| *sample = 0.0);
+                self.scratch_r[..samples].iter_mut().for_each(|sample| *sample = 0.0);
+            },
+            1 => {
+                self.scratch_l[..samples].copy_from_slice(&inputs.get(0)[..samples]);
+                self.scratch_r[..samples].copy_from_slice(&inputs.get(0)[..samples]);
+            },
+            _ => {
+                self.scratch_l[..samples].copy_from_slice(&inputs.get(0)[..samples]);
+                self.scratch_r[..samples].copy_from_slice(&inputs.get(1)[..samples]);
+            },
+        }
+        // the sidechain, if there is one. Hosts that don't route anything to it usually still give us 4 inputs,
+        // just silent ones, and there's no telling that apart from a quiet kick drum. That's why
+        // Duck Source has to be set to Sidechain on purpose rather than it being used whenever it's there
+        self.has_sidechain = inputs.len() >= 4;
+        if self.has_sidechain {
+            self.sidechain_l[..samples].copy_from_slice(&inputs.get(2)[..samples]);
+            self.sidechain_r[..samples].copy_from_slice(&inputs.get(3)[..samples]);
+        }
+
+        // with fewer than 2 outputs there's nowhere to put a stereo delay, so just pass the input through
+        if outputs.len() < 2 {
+            if outputs.len() == 1 {
+                outputs.get_mut(0)[..samples].copy_from_slice(&self.scratch_l[..samples]);
+            }
+            return;
+        }
+
+        // get_mut gives us each output channel as a mutable slice
+        let out_l = outputs.get_mut(0);
+        let out_r = outputs.get_mut(1);
+        self.process_scratch(&mut out_l[..samples], &mut out_r[..samples]);
+     }
+}
+
+fn mix_samples(original: f32, added: f32, amount: f32, invert_dry: bool) -> f32 {
+    // always ensures that there's never more than 100%
+    // if dry_wet (amount) is 60%, dry amount is 0.4, wet is 0.6
+    // With invert_dry the dry is subtracted instead of added, so anything the dry and wet have in common cancels.
+    // Careful: at 50% dry/wet, if the wet is the same as the dry (e.g. feedback-free delay on a steady tone
+    // that lines up with the delay time) the output goes completely silent. That's the point of it,
+    // but it can look like the plugin is broken if you don't know it's on.
+    let dry = if invert_dry { amount - 1.0 } else { 1.0 - amount };
+    // return the mixed value
+    (original*dry) + (added*amount)
+}
+
+// Mix Lock's crossfade. Equal power instead of straight lines, so with dry and wet at the same level
+// (LevelMatch sees to that) the output is just as loud at 50% as at either end. That's exact as long as
+// the dry and wet aren't alike, which for a delay they mostly aren't. If they are (a steady tone that lines
+// up with the delay time) the middle comes out up to 3dB louder.
+fn mix_samples_locked(original: f32, added: f32, amount: f32, invert_dry: bool) -> f32 {
+    let angle = amount * std::f32::consts::FRAC_PI_2;
+    let dry = if invert_dry { -angle.cos() } else { angle.cos() };
+    (original * dry) + (added * angle.sin())
+}
+
+// Mid is what both sides have in common, side is the difference between them.
+// Halving on the way in means from_mid_side gets back exactly what went in (l = m + s, r = m - s).
+// Since the delay line and feedback treat both channels the same, running them on mid/side sounds the
+// same as left/right on its own (a mono input is just mid with no side either way). It only makes a difference
+// once something in the loop treats the two channels differently.
+fn to_mid_side(left: f32, right: f32) -> (f32, f32) {
+    ((left + right) * 0.5, (left - right) * 0.5)
+}
+
+fn from_mid_side(mid: f32, side: f32) -> (f32, f32) {
+    (mid + side, mid - side)
+}
+
+// same idea as reload_delay_buffer, but for the lookahead. Can be empty, which means no lookahead.
+fn reload_lookahead_buffer(sample_rate: f32, lookahead_time: f32) -> CircularBuffer<(f32, f32)> {
+    CircularBuffer::with_default(lookahead_samples(sample_rate, lookahead_time), (0f32, 0f32))
+}
+
+// how many samples of lookahead the Lookahead knob asks for
+fn lookahead_samples(sample_rate: f32, lookahead_time: f32) -> usize {
+    (sample_rate * lookahead_time * MAX_LOOKAHEAD_TIME) as usize
+}
+
+// The coefficient for a one-pole smoother (x += (target - x) * coeff) that gets about 63% of the way
+// to its target in `time` seconds, whatever the sample rate. Anything shorter than a sample is just 1 sample.
+// Everything that smooths should get its coefficient from here, and remake it in set_sample_rate.
+// exp_m1 rather than 1 - exp: at 192kHz a slow smoother's exp() is so close to 1 that f32 keeps barely
+// two digits of the difference.
+fn smoothing_coeff(time: f32, sample_rate: f32) -> f32 {
+    -(-1.0 / (time * sample_rate).max(1.0)).exp_m1()
+}
+
+// the trims go from -TRIM_RANGE_DB to +TRIM_RANGE_DB, with 0dB in the middle
+fn trim_db(value: f32) -> f32 {
+    (value * 2.0 - 1.0) * TRIM_RANGE_DB
+}
+
+fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+// Constant power pan, 0 to 1 (left to right), as (left gain, right gain).
+// Scaled so the centre is 1 on both sides, ie. no change. Hard left is +3dB on the left and nothing on the right.
+// left² + right² is always 2, so the total power is the same wherever it's panned.
+// (Dividing by the centre's gain rather than multiplying by sqrt(2) makes the centre exactly 1, not 0.99999994.)
+fn pan_law(pan: f32) -> (f32, f32) {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+    let angle = pan * FRAC_PI_2;
+    let centre = FRAC_PI_4.cos();
+    (angle.cos() / centre, (FRAC_PI_2 - angle).cos() / centre)
+}
+
+// switches are stored as bools but the host only knows about 0 to 1
+fn switch_value(on: bool) -> f32 {
+    if on { 1.0 } else { 0.0 }
+}
+
+// and what a switch shows as its text
+fn on_off(on: bool) -> String {
+    if on { "On".to_string() } else { "Off".to_string() }
+}
+
+// the smallest prime that's at least n (and at least 2).
+// is_multiple_of would read better, but it needs Rust 1.87, and this is the only thing that would
+#[allow(clippy::manual_is_multiple_of)]
+fn next_prime(n: usize) -> usize {
+    let is_prime = |x: usize| (2..).take_while(|d| d * d <= x).all(|d| x % d != 0);
+    (n.max(2)..).find(|&x| is_prime(x)).unwrap()
+}
+
+// What the delay lines hold and the feedback is worked out in. Everything else is f32, but with the "f64-feedback"
+// feature this is f64: a long tail goes round the loop thousands of times, and in f32 every trip rounds it a little.
+// The host still only ever sees f32. Anything in the loop that isn't plain arithmetic (the filters, BBD, lo-fi,
+// saturation and so on) still works in f32, so with any of those on, that part of the loop gets rounded anyway
+#[cfg(not(feature = "f64-feedback"))]
+type LoopSample = f32;
+#[cfg(feature = "f64-feedback")]
+type LoopSample = f64;
+
+// Going the other way needs a cast. Without the feature it's f32 to f32, which does nothing
+#[allow(clippy::unnecessary_cast)]
+fn loop_to_f32(sample: LoopSample) -> f32 {
+    sample as f32
+}
+
+// A stereo delay line, as made by reload_delay_buffer. It's a ring buffer: write puts in the newest sample,
+// and read can look back any distance up to the size of it, including between two samples, so a delay
+// doesn't have to be a whole number of them
+struct DelayLine {
+    samples: Vec<(LoopSample, LoopSample)>,
+    // where the next write goes, which is also where the oldest sample is
+    position: usize,
+    // how far back read is normally asked to look, in samples
+    delay: f32,
+}
+
+impl DelayLine {
+    // spare is how much further back than delay read might have to look
+    fn new(delay: f32, spare: f32) -> DelayLine {
+        DelayLine {
+            samples: vec![(0.0, 0.0); DelayLine::size_for(delay + spare)],
+            position: 0,
+            delay,
+        }
+    }
+
+    // enough for a read that far back, plus one more either side for Cubic. Capped in case of a ridiculous
+    // sample rate, so we never try to allocate gigabytes
+    fn size_for(back: f32) -> usize {
+        (back.ceil() as usize + 2).min(MAX_DELAY_BUFFER_SIZE)
+    }
+
+    fn delay(&self) -> f32 {
+        self.delay
+    }
+
+    fn capacity(&self) -> usize {
+        self.samples.len()
+    }
+
+    // What was written `back` samples ago, so 1 is the last write. Each side can read from somewhere different.
+    // Anything under 1 would be reading something that hasn't gone in yet, so it's held at 1. The far end
+    // leaves room for Cubic's extra sample
+    fn read(&self, back: (f32, f32), interpolation: Interpolation) -> (LoopSample, LoopSample) {
+        (self.read_side(back.0, interpolation, |sample| sample.0), self.read_side(back.1, interpolation, |sample| sample.1))
+    }
+
+    fn read_side(&self, back: f32, interpolation: Interpolation, side: impl Fn((LoopSample, LoopSample)) -> LoopSample) -> LoopSample {
+        let size = self.samples.len();
+        let back = back.clamp(1.0, (size - 2) as f32);
+        let whole = back as usize;
+        let fraction = LoopSample::from(back - whole as f32);
+        let sample = |back: usize| side(self.samples[(self.position + size - back) % size]);
+        // there's nothing newer than the last write, so that stands in for the one before it
+        interpolation.read(sample((whole - 1).max(1)), sample(whole), sample(whole + 1), sample(whole + 2), fraction)
+    }
+
+    // The last `delay` samples (to the nearest whole one), oldest first, fitted to `out` for buffer_snapshot.
+    // Each point covers its share of them and gets whichever is furthest from 0. With more points than samples,
+    // each sample covers a few points instead
+    fn snapshot(&self, out: &mut [f32], mono: impl Fn((LoopSample, LoopSample)) -> f32) {
+        let span = self.delay.round().max(1.0) as usize;
+        let points = out.len();
+        for (point, out_s) in out.iter_mut().enumerate() {
+            let start = point * span / points;
+            let end = ((point + 1) * span / points).max(start + 1);
+            *out_s = (start..end)
+                .map(|oldest| {
+                    let back = (span - oldest) as f32;
+                    mono(self.read((back, back), Interpolation::None))
+                })
+                .fold(0.0, |loudest, sample| if sample.abs() > loudest.abs() { sample } else { loudest });
+        }
+    }
+
+    fn write(&mut self, sample: (LoopSample, LoopSample)) {
+        self.samples[self.position] = sample;
+        self.position = (self.position + 1) % self.samples.len();
+    }
+}
+
+fn reload_delay_buffer(sample_rate: f32, delay_time: f32) -> DelayLine {
+    // by having this in one place, it reduces the amount of places where DelayLine is made
+    // and it doesn't need to have access to delay_time or sample_rate directly from SillyDelay
+    // in case, for example, they're not initialized yet
+    // A problem with this is that any time delay time is changed the whole buffer is cleaned out.
+
+    // A sample rate is always in (kilo)Hertz, ie. per Second. Pretty obvious, but I forgot for a moment earlier.
+    // So to ensure a maximum of 2 seconds - the size of our delay_buffer has to be the sample rate times 2.
+    // (delay_time can only go up to 1.0 maximum)
+    // if the delay time chosen is less than that, for example, 200ms, we need to use a smaller delay_buffer
+    // hence rate * time * 2
+    // (see delay_samples)
+    // buffer starts out full of 0s, so the first time round the wet is silence.
+    // It has room for Motion to read as far back as it goes, too
+    DelayLine::new(delay_samples(sample_rate, delay_time), MOTION_MAX_OFFSET * sample_rate)
+}
+
+// Knobs for times and frequencies go from min to max exponentially, so every bit of the knob moves it
+// by the same ratio: halfway is the geometric mean (sqrt(min * max)), not the average. min can be above max,
+// which turns the knob round. log_to_lin goes back the other way
+fn lin_to_log(value: f32, min: f32, max: f32) -> f32 {
+    min * (max / min).powf(value)
+}
+
+fn log_to_lin(real: f32, min: f32, max: f32) -> f32 {
+    (real / min).ln() / (max / min).ln()
+}
+
+// The Delay Time knobs run from 0.1ms to 2000ms logarithmically, so the short delays get as much of the knob
+// as the long ones. These go between the knob and the delay (0 to 1 of 2000ms, like delay_time)
+fn knob_to_delay(knob: f32) -> f32 {
+    let (min, max) = Param::DelayTime.range();
+    lin_to_log(knob, min, max).clamp(min, max)
+}
+
+fn delay_to_knob(delay_time: f32) -> f32 {
+    let (min, max) = Param::DelayTime.range();
+    log_to_lin(delay_time, min, max).clamp(0.0, 1.0)
+}
+
+// the same for Delay in Hz, which is logarithmic too (log_to_lin goes back)
+fn knob_to_hz(knob: f32) -> f32 {
+    lin_to_log(knob, TUNED_LOWEST, TUNED_HIGHEST).clamp(TUNED_LOWEST, TUNED_HIGHEST)
+}
+
+// MIDI note number to Hz, with A4 (69) at 440
+fn note_to_hz(note: f32) -> f32 {
+    440.0 * 2f32.powf((note - 69.0) / 12.0)
+}
+
+// the nearest note to a frequency, eg. "A4" for 440Hz
+fn note_name(freq: f32) -> String {
+    const NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+    let note = (69.0 + 12.0 * (freq / 440.0).log2()).round() as i32;
+    format!("{}{}", NAMES[note.rem_euclid(12) as usize], note.div_euclid(12) - 1)
+}
+
+// how many samples a delay is. Not rounded, since DelayLine can read between samples
+fn delay_samples(sample_rate: f32, delay_time: f32) -> f32 {
+    let samples = sample_rate * delay_time * 2.;
+    // A delay that's come through knob_to_delay can be a hair off a whole number of samples, and reading
+    // between two samples for that would dull every echo for nothing. So anything that close is snapped to it.
+    // The knob's rounding is in proportion to the delay, about a sample at 2 seconds and 192k
+    let snap = DELAY_SNAP.max(samples * DELAY_SNAP_RELATIVE);
+    let samples = if (samples - samples.round()).abs() < snap { samples.round() } else { samples };
+    // Capped anyway in case of a ridiculous sample rate, so the buffer stays under MAX_DELAY_BUFFER_SIZE.
+    // It's always at least 1 sample though. The loop reads before it writes, so anything shorter would be
+    // asking for a sample that isn't there yet. That's anything under about 0.02ms at 44.1k
+    samples.clamp(1.0, (MAX_DELAY_BUFFER_SIZE - 2) as f32)
+}
+
+// necessary to compile to VST
+plugin_main!(SillyDelay);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a sine at `freq` Hz and `level` peak, `len` samples long
+    fn sine(sample_rate: f32, freq: f32, level: f32, len: usize) -> Vec<f32> {
+        (0..len).map(|i| (i as f32 * freq * std::f32::consts::TAU / sample_rate).sin() * level).collect()
+    }
+
+    // the loudest sample, either way up
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0, |peak, sample| peak.max(sample.abs()))
+    }
+
+    // runs a block through and hands back the output
+    fn render(delay: &mut SillyDelay, input_l: &[f32], input_r: &[f32]) -> (Vec<f32>, Vec<f32>) {
+        let (mut out_l, mut out_r) = (vec![0.0; input_l.len()], vec![0.0; input_r.len()]);
+        delay.process_block(input_l, input_r, &mut out_l, &mut out_r);
+        (out_l, out_r)
+    }
+
+    // Runs a block through process, the way a host does: one pointer per channel. The same pointer
+    // can be both an input and an output, like a host that processes in place
+    fn process_raw(delay: &mut SillyDelay, inputs: &[*const f32], outputs: &mut [*mut f32], samples: usize) {
+        let mut buffer = unsafe {
+            AudioBuffer::from_raw(inputs.len(), outputs.len(), inputs.as_ptr(), outputs.as_mut_ptr(), samples)
+        };
+        delay.process(&mut buffer);
+    }
+
+    #[test]
+    fn meters_follow_input_wet_and_output() {
+        let mut delay = SillyDelay::builder().dry_wet(0.5).delay_ms(10.0).build().unwrap();
+        let meters = delay.meters();
+        assert_eq!((meters.input, meters.wet, meters.output), (0.0, 0.0, 0.0));
+        let input = sine(44100.0, 100.0, 0.5, 4410);
+        render(&mut delay, &input, &input);
+        let meters = delay.meters();
+        assert!((meters.input - 0.5).abs() < 0.001, "input {}", meters.input);
+        // the wet is the input 10ms later, with no feedback to speak of
+        assert!((meters.wet - 0.5).abs() < 0.01, "wet {}", meters.wet);
+        // half of each, and 10ms is one whole cycle at 100Hz, so they add up to the input again
+        assert!((meters.output - 0.5).abs() < 0.01, "output {}", meters.output);
+        assert!(!delay.input_clipped() && !delay.output_clipped());
+    }
+
+    #[test]
+    fn in_place_processing_matches_separate_buffers() {
+        let settings = SillyDelay::builder().dry_wet(0.5).delay_ms(5.0).feedback(0.6);
+        let (mut separate, mut in_place) = (settings.build().unwrap(), settings.build().unwrap());
+        let input_l = sine(44100.0, 300.0, 0.5, 1024);
+        let input_r = sine(44100.0, 500.0, 0.5, 1024);
+
+        let (mut out_l, mut out_r) = (vec![0.0; 1024], vec![0.0; 1024]);
+        process_raw(&mut separate, &[input_l.as_ptr(), input_r.as_ptr()], &mut [out_l.as_mut_ptr(), out_r.as_mut_ptr()], 1024);
+
+        // the output overwrites the input as it goes
+        let (mut both_l, mut both_r) = (input_l.clone(), input_r.clone());
+        let (l, r) = (both_l.as_mut_ptr(), both_r.as_mut_ptr());
+        process_raw(&mut in_place, &[l, r], &mut [l, r], 1024);
+
+        assert_eq!((both_l, both_r), (out_l, out_r));
+    }
+
+    #[test]
+    fn decorrelate_splits_identical_sides() {
+        let mut delay = SillyDelay::builder().delay_ms(5.0).build().unwrap();
+        let input = sine(44100.0, 300.0, 0.5, 4410);
+        let (out_l, out_r) = render(&mut delay, &input, &input);
+        assert_eq!(out_l, out_r);
+
+        delay.set_parameter(Param::Decorrelate.to_i32(), 1.0);
+        let (out_l, out_r) = render(&mut delay, &input, &input);
+        let difference = out_l.iter().zip(&out_r).fold(0.0f32, |most, (l, r)| most.max((l - r).abs()));
+        assert!(difference > 0.1, "largest difference {}", difference);
+    }
+
+    #[test]
+    fn exported_settings_import_unchanged() {
+        let mut original = SillyDelay::default();
+        // somewhere away from every default, in steps that aren't round numbers
+        for param in Param::ALL {
+            original.set_parameter(param.to_i32(), (param.to_i32() as f32 * 0.377).fract());
+        }
+        let text = original.export_settings();
+
+        let mut imported = SillyDelay::default();
+        imported.import_settings(&text).unwrap();
+        for param in Param::ALL {
+            assert_eq!(imported.get_parameter(param.to_i32()), original.get_parameter(param.to_i32()), "{}", param.name());
+        }
+        assert_eq!(imported.export_settings(), text);
+    }
+
+    #[test]
+    fn mid_side_round_trip_is_transparent() {
+        for (left, right) in [(0.5, 0.5), (1.0, -1.0), (0.25, 0.0), (-0.75, 0.125)] {
+            let (mid, side) = to_mid_side(left, right);
+            assert_eq!(from_mid_side(mid, side), (left, right));
+        }
+    }
+
+    #[test]
+    fn mid_side_loop_sounds_like_left_right() {
+        let settings = SillyDelay::builder().delay_ms(5.0).feedback(0.7).dry_wet(0.5);
+        let (mut left_right, mut mid_side) = (settings.build().unwrap(), settings.build().unwrap());
+        mid_side.set_parameter(Param::StereoMode.to_i32(), 1.0);
+        // switching empties the buffer first, so let that finish before comparing
+        let silence = vec![0.0; 4410];
+        render(&mut mid_side, &silence, &silence);
+        render(&mut left_right, &silence, &silence);
+
+        let (input_l, input_r) = (sine(44100.0, 300.0, 0.5, 4410), sine(44100.0, 700.0, 0.3, 4410));
+        let (out_l, out_r) = render(&mut left_right, &input_l, &input_r);
+        let (ms_l, ms_r) = render(&mut mid_side, &input_l, &input_r);
+        for (a, b) in out_l.iter().chain(&out_r).zip(ms_l.iter().chain(&ms_r)) {
+            assert!((a - b).abs() < 1e-5, "{} vs {}", a, b);
+        }
+    }
+
+    #[test]
+    fn switching_stereo_mode_fades_the_old_buffer_out() {
+        // everything that can turn mid/side on
+        for (param, value) in [(Param::StereoMode, 1.0), (Param::ChannelMode, ChannelMode::MidSide.to_param()), (Param::SideOnly, 1.0)] {
+            let mut delay = SillyDelay::builder().delay_ms(5.0).feedback(0.9).build().unwrap();
+            let input = sine(44100.0, 300.0, 0.5, 4410);
+            let (before, _) = render(&mut delay, &input, &input);
+
+            delay.set_parameter(param.to_i32(), value);
+            // the old buffer (and its feedback) fade out, and nothing jumps on the way
+            let silence = vec![0.0; 4410];
+            let (after, _) = render(&mut delay, &silence, &silence);
+            let largest_step = |samples: &[f32]| samples.windows(2).fold(0.0f32, |most, pair| most.max((pair[1] - pair[0]).abs()));
+            assert!(largest_step(&after) <= largest_step(&before) * 1.1, "{}", param.name());
+            assert!(delay.buffer_mid_side, "{}", param.name());
+            assert!(after[after.len() - 100..].iter().all(|&sample| sample == 0.0), "{}", param.name());
+        }
+    }
+
+    // where the first and the last sample over `threshold` are
+    fn first_and_last_above(samples: &[f32], threshold: f32) -> (Option<usize>, Option<usize>) {
+        (samples.iter().position(|sample| sample.abs() > threshold), samples.iter().rposition(|sample| sample.abs() > threshold))
+    }
+
+    #[test]
+    fn dry_and_wet_stay_aligned_with_lookahead() {
+        let mut delay = SillyDelay::builder().delay_ms(10.0).dry_wet(0.5).build().unwrap();
+        delay.set_parameter(Param::Lookahead.to_i32(), 1.0);
+        let latency = delay.get_info().initial_delay as usize;
+        assert_eq!(latency, 441);
+
+        let mut impulse = vec![0.0; 4410];
+        impulse[1000] = 1.0;
+        let (out_l, _) = render(&mut delay, &impulse, &impulse);
+        // the dry comes out as late as the host was told, and the wet exactly one delay after that
+        assert_eq!(out_l[1000 + latency], 0.5);
+        assert_eq!(out_l[1000 + latency + 441], 0.5);
+        assert_eq!(first_and_last_above(&out_l, 0.001), (Some(1000 + latency), Some(1000 + latency + 441)));
+    }
+
+    #[test]
+    fn silly_delay_times_keep_the_buffer_bounded() {
+        let mut delay = SillyDelay::default();
+        delay.set_parameter(Param::DelayTime.to_i32(), 100.0);
+        assert_eq!(delay.get_parameter(Param::DelayTime.to_i32()), 1.0);
+        // the new buffer waits for the Clear to finish, set_sample_rate makes it straight away
+        delay.set_sample_rate(44100.0);
+        // two seconds, Motion's furthest (132.3 samples) and a sample either side for reading between them
+        assert_eq!(delay.buffer_samples(), 88200 + 133 + 2);
+        assert_eq!(delay.buffer_samples(), delay.max_buffer_samples());
+
+        delay.set_parameter(Param::DelayTime.to_i32(), f32::NAN);
+        assert_eq!(delay.get_parameter(Param::DelayTime.to_i32()), 0.0);
+
+        // past 384k two seconds would be more than MAX_DELAY_BUFFER_SIZE
+        delay.set_parameter(Param::DelayTime.to_i32(), 1.0);
+        delay.set_sample_rate(768000.0);
+        assert_eq!(delay.buffer_samples(), MAX_DELAY_BUFFER_SIZE);
+    }
+
+    #[test]
+    fn lookahead_ducks_before_the_transient() {
+        let mut delay = SillyDelay::builder().delay_ms(50.0).feedback(0.95).dry_wet(0.5).build().unwrap();
+        delay.set_parameter(Param::DuckDepth.to_i32(), 1.0);
+        delay.set_parameter(Param::Lookahead.to_i32(), 1.0);
+        let latency = delay.get_info().initial_delay as usize;
+
+        // one delay's worth of tone, so the echoes carry on steadily once it stops, then something loud
+        let mut input = sine(44100.0, 400.0, 0.5, 2205);
+        input.resize(8000, 0.0);
+        input.extend(sine(44100.0, 1000.0, 1.0, 441));
+        input.resize(12000, 0.0);
+        let (out_l, _) = render(&mut delay, &input, &input);
+
+        // Nothing of the hit itself is out yet, but the echoes are already on their way down. The ducker
+        // has a 1ms attack, so give it a little over that
+        let (hit, output_hit) = (8000, 8000 + latency);
+        assert!(peak(&out_l[hit + 100..output_hit]) < peak(&out_l[hit - 400..hit]) * 0.5);
+        assert!(peak(&out_l[hit + 200..output_hit]) < peak(&out_l[hit - 400..hit]) * 0.2);
+        assert!(peak(&out_l[output_hit..output_hit + 50]) > 0.4);
+    }
+
+    // vst 0.1.0 never passes the host's bypass on, so saying we do it ourselves would leave the host's
+    // bypass button doing nothing
+    #[test]
+    fn bypass_is_left_to_the_host() {
+        assert!(matches!(SillyDelay::default().can_do(CanDo::Bypass), Supported::Maybe));
+    }
+
+    #[test]
+    fn input_routing_options() {
+        let (input_l, input_r) = (vec![0.5; 1000], vec![-0.25; 1000]);
+        for (routing, expected) in [
+            (InputRouting::Normal, (0.5, -0.25)),
+            (InputRouting::Swap, (-0.25, 0.5)),
+            (InputRouting::Mono, (0.125, 0.125)),
+        ] {
+            // both the dry and what goes into the delay get the routed input
+            for dry_wet in [0.0, 1.0] {
+                let mut delay = SillyDelay::builder().delay_ms(5.0).dry_wet(dry_wet).build().unwrap();
+                delay.set_parameter(Param::InputRouting.to_i32(), routing.to_param());
+                let (out_l, out_r) = render(&mut delay, &input_l, &input_r);
+                assert_eq!((out_l[999], out_r[999]), expected, "{:?} at {}", routing, dry_wet);
+            }
+        }
+    }
+
+    #[test]
+    fn clear_fades_the_wet_out_smoothly() {
+        // no feedback, so the wet is steady and anything that changes is the fade
+        let mut delay = SillyDelay::builder().delay_ms(20.0).build().unwrap();
+        let input = vec![0.5; 4410];
+        render(&mut delay, &input, &input);
+
+        delay.set_parameter(Param::Clear.to_i32(), 1.0);
+        let (out_l, _) = render(&mut delay, &input, &input);
+        let silent = out_l.iter().position(|&sample| sample == 0.0).unwrap();
+        assert!(out_l[..silent].windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!(silent as f32 <= CLEAR_FADE_TIME * 44100.0 + 1.0);
+        assert!(out_l[0] > 0.49);
+    }
+
+    #[test]
+    fn every_param_has_consistent_metadata() {
+        let delay = SillyDelay::default();
+        assert_eq!(delay.get_info().parameters as usize, Param::ALL.len());
+        for (index, &param) in Param::ALL.iter().enumerate() {
+            assert_eq!(param.to_i32(), index as i32);
+            assert_eq!(Param::from_i32(index as i32), Some(param));
+            assert!(!param.name().is_empty());
+            assert_eq!(Param::ALL.iter().filter(|other| other.name() == param.name()).count(), 1, "{}", param.name());
+            assert_eq!(delay.get_parameter_name(index as i32), param.name());
+            assert_eq!(delay.get_parameter_label(index as i32), param.label());
+            // a fresh plugin starts where default() says, and that's somewhere set_parameter would keep
+            let (min, max) = param.range();
+            assert!(min < max);
+            assert!((0.0..=1.0).contains(&param.default()), "{}", param.name());
+            assert_eq!(delay.get_parameter(index as i32), param.default(), "{}", param.name());
+        }
+        assert_eq!(Param::from_i32(-1), None);
+        assert_eq!(Param::from_i32(Param::ALL.len() as i32), None);
+    }
+
+    #[test]
+    fn mix_samples_ends_and_middle() {
+        for (original, added) in [(0.3, -0.7), (1.0, 0.25), (-0.5, -0.5)] {
+            assert_eq!(mix_samples(original, added, 0.0, false), original);
+            assert_eq!(mix_samples(original, added, 1.0, false), added);
+            assert_eq!(mix_samples(original, added, 0.5, false), (original + added) / 2.0);
+        }
+    }
+
+    #[test]
+    fn mix_samples_in_between() {
+        for (amount, expected) in [(0.25, 0.75 * 0.8 + 0.25 * -0.4), (0.6, 0.4 * 0.8 + 0.6 * -0.4), (0.9, 0.1 * 0.8 + 0.9 * -0.4)] {
+            assert!((mix_samples(0.8, -0.4, amount, false) - expected).abs() < 1e-6, "{}", amount);
+        }
+    }
+
+    #[test]
+    fn mix_samples_inverted_dry() {
+        assert_eq!(mix_samples(0.3, -0.7, 0.0, true), -0.3);
+        assert_eq!(mix_samples(0.3, -0.7, 1.0, true), -0.7);
+        // at 50% the same signal on both sides cancels out completely
+        assert_eq!(mix_samples(0.6, 0.6, 0.5, true), 0.0);
+        assert!((mix_samples(0.8, -0.4, 0.25, true) - (-0.75 * 0.8 + 0.25 * -0.4)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn mix_samples_locked_is_equal_power() {
+        assert_eq!(mix_samples_locked(0.3, -0.7, 0.0, false), 0.3);
+        assert!((mix_samples_locked(0.3, -0.7, 1.0, false) - -0.7).abs() < 1e-6);
+        assert!((mix_samples_locked(0.3, -0.7, 1.0, true) - -0.7).abs() < 1e-6);
+        assert_eq!(mix_samples_locked(0.3, -0.7, 0.0, true), -0.3);
+        // the dry and wet gains squared always add up to 1
+        for amount in [0.1, 0.25, 0.5, 0.8] {
+            let (dry, wet) = (mix_samples_locked(1.0, 0.0, amount, false), mix_samples_locked(0.0, 1.0, amount, false));
+            assert!((dry * dry + wet * wet - 1.0).abs() < 1e-6, "{}", amount);
+            assert_eq!(mix_samples_locked(1.0, 0.0, amount, true), -dry);
+        }
+        assert!((mix_samples_locked(1.0, 0.0, 0.5, false) - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn mono_and_missing_channels_dont_crash() {
+        let input = sine(44100.0, 300.0, 0.5, 512);
+        let mut delay = SillyDelay::builder().dry_wet(0.0).build().unwrap();
+
+        // one input goes to both sides
+        let (mut out_l, mut out_r) = (vec![0.0; 512], vec![0.0; 512]);
+        process_raw(&mut delay, &[input.as_ptr()], &mut [out_l.as_mut_ptr(), out_r.as_mut_ptr()], 512);
+        assert_eq!(out_l, out_r);
+        assert!(peak(&out_l) > 0.4);
+
+        // no input at all is silence
+        process_raw(&mut delay, &[], &mut [out_l.as_mut_ptr(), out_r.as_mut_ptr()], 512);
+        assert_eq!(peak(&out_l), 0.0);
+
+        // one output just gets the input
+        let mut out = vec![0.0; 512];
+        process_raw(&mut delay, &[input.as_ptr(), input.as_ptr()], &mut [out.as_mut_ptr()], 512);
+        assert_eq!(out, input);
+    }
+
+    #[test]
+    fn fewer_bits_quantize_the_wet() {
+        let mut delay = SillyDelay::builder().delay_ms(5.0).build().unwrap();
+        delay.set_parameter(Param::Bits.to_i32(), 1.0);
+        let bits = delay.crush_bits();
+        assert_eq!(bits, MIN_CRUSH_BITS);
+        let step = 2.0 / 2f32.powf(bits);
+
+        let input = sine(44100.0, 300.0, 0.5, 4410);
+        let (out_l, _) = render(&mut delay, &input, &input);
+        // past the fade in at the start, every sample is on a step
+        let levels: Vec<f32> = out_l[1000..].iter().map(|sample| sample / step).collect();
+        assert!(levels.iter().all(|level| level.fract() == 0.0));
+        assert!(levels.iter().any(|&level| level != 0.0));
+    }
+
+    #[test]
+    fn downsampling_holds_each_sample() {
+        let mut delay = SillyDelay::builder().delay_ms(5.0).build().unwrap();
+        delay.set_parameter(Param::Downsample.to_i32(), 1.0);
+        let factor = delay.downsample_factor();
+        assert!(factor > 1);
+
+        let input = sine(44100.0, 300.0, 0.5, 4410);
+        let (out_l, _) = render(&mut delay, &input, &input);
+        let changes = out_l[1000..].windows(2).filter(|pair| pair[0] != pair[1]).count();
+        assert!(changes <= 3410 / factor + 1, "{} changes", changes);
+    }
+
+    fn impulse(len: usize, at: usize) -> Vec<f32> {
+        let mut samples = vec![0.0; len];
+        samples[at] = 1.0;
+        samples
+    }
+
+    #[test]
+    fn network_tail_decays() {
+        let mut delay = SillyDelay::builder().delay_ms(40.0).feedback(1.0).build().unwrap();
+        delay.set_parameter(Param::Network.to_i32(), 1.0);
+        let (out_l, out_r) = render(&mut delay, &impulse(44100 * 4, 1000), &vec![0.0; 44100 * 4]);
+        let second = |n: usize| peak(&out_l[44100 * n..44100 * (n + 1)]).max(peak(&out_r[44100 * n..44100 * (n + 1)]));
+        assert!(second(0) > 0.1);
+        // every second it keeps dying away, and by the end it's well down
+        assert!((1..4).all(|n| second(n) < second(n - 1)));
+        assert!(second(3) < second(0) * 0.01);
+        assert!(out_l.iter().chain(&out_r).all(|sample| sample.is_finite()));
+    }
+
+    #[test]
+    fn over_unity_lights_the_clip_flags() {
+        let mut delay = SillyDelay::builder().dry_wet(0.0).build().unwrap();
+        let quiet = vec![0.5; 1000];
+        render(&mut delay, &quiet, &quiet);
+        assert!(!delay.input_clipped() && !delay.output_clipped());
+
+        let mut loud = quiet.clone();
+        loud[500] = 1.5;
+        render(&mut delay, &loud, &quiet);
+        assert!(delay.input_clipped() && delay.output_clipped());
+
+        // and they stay lit for CLIP_HOLD_TIME, then go out
+        let silence = vec![0.0; (CLIP_HOLD_TIME * 44100.0) as usize + 1];
+        render(&mut delay, &silence, &silence);
+        assert!(!delay.input_clipped() && !delay.output_clipped());
+    }
+
+    #[test]
+    fn smoothing_time_is_the_same_at_any_sample_rate() {
+        for sample_rate in [44100.0, 96000.0] {
+            let coeff = smoothing_coeff(0.01, sample_rate);
+            // one time constant in, a step has got 1 - 1/e of the way there
+            let mut smoothed = 0.0;
+            for _ in 0..(0.01 * sample_rate) as usize {
+                smoothed += (1.0 - smoothed) * coeff;
+            }
+            assert!((smoothed - (1.0 - (-1.0f32).exp())).abs() < 1e-3, "{} at {}", smoothed, sample_rate);
+        }
+        // anything shorter than a sample counts as one sample
+        assert_eq!(smoothing_coeff(0.0, 44100.0), -(-1.0f32).exp_m1());
+    }
+
+    #[test]
+    fn a_whole_block_matches_one_sample_at_a_time() {
+        let settings = SillyDelay::builder().delay_ms(7.0).feedback(0.7).dry_wet(0.4);
+        let (mut whole, mut single) = (settings.build().unwrap(), settings.build().unwrap());
+        for delay in [&mut whole, &mut single] {
+            delay.set_parameter(Param::Decorrelate.to_i32(), 0.5);
+            delay.set_parameter(Param::TremoloDepth.to_i32(), 0.5);
+        }
+        let (input_l, input_r) = (sine(44100.0, 300.0, 0.5, 2048), sine(44100.0, 450.0, 0.5, 2048));
+
+        let (out_l, out_r) = render(&mut whole, &input_l, &input_r);
+        let (mut single_l, mut single_r) = (vec![0.0; 2048], vec![0.0; 2048]);
+        for i in 0..2048 {
+            single.process_block(&input_l[i..=i], &input_r[i..=i], &mut single_l[i..=i], &mut single_r[i..=i]);
+        }
+        assert_eq!((out_l, out_r), (single_l, single_r));
+    }
+
+    #[test]
+    fn lifecycle_calls_in_odd_orders() {
+        let input = sine(44100.0, 300.0, 0.5, 1024);
+
+        // processing straight away, before the host has said anything
+        let mut delay = SillyDelay::default();
+        let (out_l, _) = render(&mut delay, &input, &input);
+        assert!(peak(&out_l) > 0.1);
+
+        // nonsense sample rates are ignored, and a block bigger than promised still works
+        let mut delay = SillyDelay::default();
+        delay.set_sample_rate(0.0);
+        delay.set_sample_rate(f32::NAN);
+        delay.set_block_size(16);
+        delay.resume();
+        let (out_l, _) = render(&mut delay, &input, &input);
+        assert!(peak(&out_l) > 0.1 && out_l.iter().all(|sample| sample.is_finite()));
+
+        // suspend, change everything, resume, in whatever order
+        delay.suspend();
+        delay.set_parameter(Param::DelayTime.to_i32(), 0.5);
+        delay.set_block_size(0);
+        delay.set_sample_rate(96000.0);
+        delay.suspend();
+        delay.resume();
+        delay.set_sample_rate(48000.0);
+        let (out_l, _) = render(&mut delay, &input, &input);
+        assert!(out_l.iter().all(|sample| sample.is_finite()));
+        assert_eq!(delay.delay_buffer.delay(), delay_samples(48000.0, knob_to_delay(0.5)));
+    }
+
+    #[test]
+    fn next_prime_rounds_up_to_a_prime() {
+        assert_eq!(next_prime(0), 2);
+        assert_eq!(next_prime(2), 2);
+        assert_eq!(next_prime(8), 11);
+        assert_eq!(next_prime(13), 13);
+        assert_eq!(next_prime(207), 211);
+        // the two decorrelation allpasses never share a length
+        assert_ne!(Allpass::new(44100.0, DECORRELATE_TIME_L).buffer.len(), Allpass::new(44100.0, DECORRELATE_TIME_R).buffer.len());
+    }
+
+    #[cfg(feature = "diagnostics")]
+    #[test]
+    fn diagnostics_record_what_happened() {
+        let mut delay = SillyDelay::default();
+        assert_eq!(delay.diagnostics().count(), 0);
+        delay.set_sample_rate(-1.0);
+        let input = vec![0.0; 4096];
+        render(&mut delay, &input, &input);
+        delay.set_sample_rate(48000.0);
+        let events: Vec<Diagnostic> = delay.diagnostics().collect();
+        assert_eq!(events, [Diagnostic::BadSampleRate(-1.0), Diagnostic::ScratchGrown(4096), Diagnostic::BuffersRebuilt]);
+
+        // only the latest DIAGNOSTICS_KEPT are kept
+        for _ in 0..DIAGNOSTICS_KEPT {
+            delay.set_sample_rate(0.0);
+        }
+        assert!(delay.diagnostics().all(|event| event == Diagnostic::BadSampleRate(0.0)));
+        assert_eq!(delay.diagnostics().count(), DIAGNOSTICS_KEPT);
+    }
+
+    #[test]
+    fn mono_safe_narrows_anti_correlated_wet() {
+        // mostly out of phase, with a little in common so there's something left once it narrows
+        let (opposite, shared) = (sine(44100.0, 300.0, 0.5, 88200), sine(44100.0, 500.0, 0.2, 88200));
+        let input_l: Vec<f32> = opposite.iter().zip(&shared).map(|(a, b)| a + b).collect();
+        let input_r: Vec<f32> = opposite.iter().zip(&shared).map(|(a, b)| b - a).collect();
+        let side = |out_l: &[f32], out_r: &[f32]| peak(&out_l[44100..].iter().zip(&out_r[44100..]).map(|(l, r)| (l - r) * 0.5).collect::<Vec<f32>>());
+
+        let mut wide = SillyDelay::builder().delay_ms(10.0).build().unwrap();
+        let (out_l, out_r) = render(&mut wide, &input_l, &input_r);
+        assert!(wide.meters().correlation < -0.5);
+        let wide_side = side(&out_l, &out_r);
+
+        let mut safe = SillyDelay::builder().delay_ms(10.0).build().unwrap();
+        safe.set_parameter(Param::MonoSafe.to_i32(), 1.0);
+        let (out_l, out_r) = render(&mut safe, &input_l, &input_r);
+        // it narrows until the correlation is back up at the floor (0 by default)
+        assert!(safe.meters().correlation > -0.1, "correlation {}", safe.meters().correlation);
+        assert!(side(&out_l, &out_r) < wide_side * 0.6);
+    }
+
+    #[test]
+    fn phase_rotate_keeps_the_level_and_moves_the_phase() {
+        for freq in [200.0, 1000.0, 5000.0] {
+            let mut delay = SillyDelay::builder().delay_ms(5.0).build().unwrap();
+            delay.set_parameter(Param::PhaseRotate.to_i32(), 0.5);
+            let input = sine(44100.0, freq, 0.5, 8820);
+            let (out_l, out_r) = render(&mut delay, &input, &input);
+            let (out_l, out_r) = (&out_l[4410..], &out_r[4410..]);
+            assert!((peak(out_r) / peak(out_l) - 1.0).abs() < 0.01, "level at {}Hz", freq);
+            let difference = out_l.iter().zip(out_r).fold(0.0f32, |most, (l, r)| most.max((l - r).abs()));
+            assert!(difference > 0.05, "phase at {}Hz", freq);
+        }
+    }
+
+    // where the middle of a signal's energy is, in samples from the start
+    fn centre(samples: &[f32]) -> f32 {
+        let total: f32 = samples.iter().map(|sample| sample.abs()).sum();
+        samples.iter().enumerate().map(|(i, sample)| i as f32 * sample.abs()).sum::<f32>() / total
+    }
+
+    #[test]
+    fn delays_come_between_samples() {
+        // 0.5ms at 44.1k is 22.05 samples, which used to be rounded down to 22
+        let mut delay = SillyDelay::builder().delay_ms(0.5).build().unwrap();
+        let (out_l, _) = render(&mut delay, &impulse(2000, 1000), &impulse(2000, 1000));
+        assert!((centre(&out_l) - 1022.05).abs() < 0.01, "{}", centre(&out_l));
+        assert!(out_l[1022] > out_l[1023] && out_l[1023] > 0.0);
+
+        // and round the loop it takes exactly one delay too, so the tenth echo is 220.5 samples in
+        let mut delay = SillyDelay::builder().delay_ms(0.5).feedback(0.7).build().unwrap();
+        delay.set_parameter(Param::ShortDelayGuard.to_i32(), 0.0);
+        let (out_l, _) = render(&mut delay, &impulse(2000, 1000), &impulse(2000, 1000));
+        let tenth = &out_l[1000 + 215..1000 + 226];
+        assert!((centre(tenth) + 215.0 - 220.5).abs() < 0.1, "{}", centre(tenth));
+
+        // a delay that is a whole number of samples comes out as one, even after the knob's rounding
+        for sample_rate in [44100, 48000, 96000, 192000] {
+            for ms in (1..=2000).filter(|ms| ms * sample_rate % 1000 == 0) {
+                let samples = delay_samples(sample_rate as f32, knob_to_delay(delay_to_knob(ms as f32 / 2000.0)));
+                assert_eq!(samples, (ms * sample_rate / 1000) as f32, "{}ms at {}", ms, sample_rate);
+            }
+        }
+    }
+
+    #[test]
+    fn repeat_limit_leaves_exactly_n_echoes() {
+        // all the way up is no limit, and they go on past the end
+        let mut delay = SillyDelay::builder().delay_ms(50.0).feedback(0.8).build().unwrap();
+        let (out_l, _) = render(&mut delay, &impulse(2205 * 8, 100), &impulse(2205 * 8, 100));
+        assert!((1..8).all(|echo| out_l[100 + echo * 2205].abs() > 0.001));
+
+        for repeats in [1, 3, 5] {
+            let mut delay = SillyDelay::builder().delay_ms(50.0).feedback(0.8).build().unwrap();
+            delay.set_parameter(Param::RepeatLimit.to_i32(), (repeats - 1) as f32 / MAX_REPEAT_LIMIT);
+            let (out_l, _) = render(&mut delay, &impulse(2205 * 8, 100), &impulse(2205 * 8, 100));
+            // each echo lands on a whole number of delays after the hit
+            let echoes = (1..8).filter(|echo| out_l[100 + echo * 2205].abs() > 0.001).count();
+            assert_eq!(echoes, repeats);
+            assert!(out_l[100 + repeats * 2205 + 1..].iter().all(|&sample| sample.abs() < 0.001));
+        }
+    }
+
+    #[test]
+    fn unity_trims_are_transparent() {
+        let input = sine(44100.0, 1000.0, 0.5, 4410);
+        let mut plain = SillyDelay::builder().delay_ms(10.0).feedback(0.5).dry_wet(0.5).build().unwrap();
+        let mut trimmed = SillyDelay::builder().delay_ms(10.0).feedback(0.5).dry_wet(0.5).build().unwrap();
+        // moved away and back again, so the gains have been worked out from the knobs rather than left as they started
+        trimmed.set_parameter(Param::InputTrim.to_i32(), 0.0);
+        trimmed.set_parameter(Param::InputTrim.to_i32(), 0.5);
+        trimmed.set_parameter(Param::OutputTrim.to_i32(), 1.0);
+        trimmed.set_parameter(Param::OutputTrim.to_i32(), 0.5);
+        assert_eq!(trimmed.get_parameter_text(Param::InputTrim.to_i32()), "0.0");
+        assert_eq!(trimmed.get_parameter_label(Param::InputTrim.to_i32()), "dB");
+        let (plain_l, _) = render(&mut plain, &input, &input);
+        let (trimmed_l, _) = render(&mut trimmed, &input, &input);
+        assert_eq!(plain_l, trimmed_l);
+
+        // the output trim is just a gain on the end
+        trimmed.set_parameter(Param::OutputTrim.to_i32(), 0.75);
+        let (plain_l, _) = render(&mut plain, &input, &input);
+        let (trimmed_l, _) = render(&mut trimmed, &input, &input);
+        let gain = db_to_gain(TRIM_RANGE_DB / 2.0);
+        assert!(plain_l.iter().zip(&trimmed_l).all(|(plain, trimmed)| (plain * gain - trimmed).abs() < 1e-5));
+
+        // and the ends are +-TRIM_RANGE_DB
+        trimmed.set_parameter(Param::OutputTrim.to_i32(), 0.0);
+        assert_eq!(trimmed.get_parameter_text(Param::OutputTrim.to_i32()), format!("{:.1}", -TRIM_RANGE_DB));
+        trimmed.set_parameter(Param::OutputTrim.to_i32(), 1.0);
+        assert_eq!(trimmed.get_parameter_text(Param::OutputTrim.to_i32()), format!("{:.1}", TRIM_RANGE_DB));
+    }
+
+    #[test]
+    fn output_fades_in_after_new_and_resume() {
+        let mut delay = SillyDelay::builder().dry_wet(0.0).build().unwrap();
+        let fade = (START_FADE_TIME * 44100.0) as usize;
+        for _ in 0..2 {
+            let (out_l, out_r) = render(&mut delay, &[1.0; 1000], &[1.0; 1000]);
+            assert!(out_l[0] < 0.01 && out_r[0] < 0.01);
+            assert!(out_l[fade / 2] > 0.3 && out_l[fade / 2] < 0.7);
+            assert!(out_l[..fade].windows(2).all(|pair| pair[0] <= pair[1]));
+            assert!(out_l[fade + 1..].iter().all(|&sample| sample == 1.0));
+            // it's only the start that fades
+            let (out_l, _) = render(&mut delay, &[1.0; 100], &[1.0; 100]);
+            assert!(out_l.iter().all(|&sample| sample == 1.0));
+            delay.suspend();
+            delay.resume();
+        }
+    }
+
+    #[test]
+    fn dry_pan_keeps_the_power_and_centre_is_unity() {
+        assert_eq!(pan_law(0.5), (1.0, 1.0));
+        for step in 0..=20 {
+            let (left, right) = pan_law(step as f32 / 20.0);
+            assert!((left * left + right * right - 2.0).abs() < 1e-5);
+        }
+
+        let input = sine(44100.0, 1000.0, 0.5, 2000);
+        let mut delay = SillyDelay::builder().dry_wet(0.0).build().unwrap();
+        delay.set_parameter(Param::DryPan.to_i32(), 0.5);
+        let (out_l, out_r) = render(&mut delay, &input, &input);
+        // past the fade in, it's exactly what went in
+        assert_eq!(out_l[500..], input[500..]);
+        assert_eq!(out_r[500..], input[500..]);
+
+        delay.set_parameter(Param::DryPan.to_i32(), 0.2);
+        let (out_l, out_r) = render(&mut delay, &input, &input);
+        let power = |samples: &[f32]| samples.iter().map(|sample| sample * sample).sum::<f32>();
+        let total = power(&out_l) + power(&out_r);
+        assert!((total / (power(&input) * 2.0) - 1.0).abs() < 1e-4);
+        assert!(peak(&out_l) > peak(&out_r));
+    }
+
+    #[test]
+    fn parameter_listener_hears_every_change() {
+        use std::sync::{Arc, Mutex};
+        let heard = Arc::new(Mutex::new(Vec::new()));
+        let mut delay = SillyDelay::default();
+        let sink = Arc::clone(&heard);
+        delay.set_parameter_listener(move |param, value| sink.lock().unwrap().push((param, value)));
+
+        delay.set_parameter(Param::Feedback.to_i32(), 0.3);
+        // it gets what was kept, not what was sent
+        delay.set_parameter(Param::DelayTime.to_i32(), 100.0);
+        // not a parameter, so there's nothing to hear
+        delay.set_parameter(-1, 0.5);
+        assert_eq!(*heard.lock().unwrap(), vec![(Param::Feedback, 0.3), (Param::DelayTime, 1.0)]);
+    }
+
+    #[test]
+    fn tremolo_moves_the_wet_at_its_rate() {
+        let mut delay = SillyDelay::builder().delay_ms(1.0).build().unwrap();
+        delay.set_parameter(Param::TremoloRate.to_i32(), log_to_lin(4.0, MIN_TREMOLO_RATE, MAX_TREMOLO_RATE));
+        assert_eq!(delay.get_parameter_text(Param::TremoloRate.to_i32()), "4.00");
+        // none at all yet, so the wet is as steady as what goes in
+        let (out_l, _) = render(&mut delay, &[0.5; 44100], &[0.5; 44100]);
+        assert!(out_l[1000..].iter().all(|&sample| (sample - 0.5).abs() < 1e-4));
+
+        delay.set_parameter(Param::TremoloDepth.to_i32(), 1.0);
+        let (out_l, _) = render(&mut delay, &[0.5; 44100], &[0.5; 44100]);
+        // all the way down to nothing and back, four times a second
+        assert!(peak(&out_l) > 0.49);
+        assert!(out_l.iter().fold(1.0f32, |least, &sample| least.min(sample)) < 0.01);
+        let rises = out_l.windows(2).filter(|pair| pair[0] < 0.25 && pair[1] >= 0.25).count();
+        assert_eq!(rises, 4);
+    }
+
+    #[test]
+    fn every_index_has_sensible_text() {
+        let mut delay = SillyDelay::default();
+        let count = delay.get_info().parameters;
+        // the ends and the middle of every knob, so each one's text gets worked out from somewhere odd
+        for value in [0.0, 0.5, 1.0] {
+            for index in 0..count {
+                delay.set_parameter(index, value);
+                assert!(!delay.get_parameter_name(index).is_empty());
+                assert!(!delay.get_parameter_text(index).is_empty(), "{}", delay.get_parameter_name(index));
+                delay.get_parameter_label(index);
+            }
+        }
+        for index in [-1, count, count + 100, i32::MAX] {
+            assert_eq!(delay.get_parameter_name(index), "");
+            assert_eq!(delay.get_parameter_label(index), "");
+            assert_eq!(delay.get_parameter_text(index), "");
+            assert_eq!(delay.get_parameter(index), 0.0);
+        }
+    }
+
+    #[test]
+    fn level_match_follows_the_level_difference() {
+        // five seconds of each is plenty for MIX_LOCK_TIME to settle
+        for (input, wet, gain) in [(0.5, 0.5, 1.0), (0.5, 0.25, 2.0), (0.25, 0.5, 0.5), (0.5, 0.05, MIX_LOCK_MAX_GAIN), (0.05, 0.5, 1.0 / MIX_LOCK_MAX_GAIN)] {
+            let mut level_match = LevelMatch::new(44100.0);
+            let mut last = 1.0;
+            for _ in 0..44100 * 5 {
+                last = level_match.process((input, input), (wet, wet));
+            }
+            assert!((last - gain).abs() < gain * 0.01, "{} {} {}", input, wet, last);
+        }
+
+        // with no wet yet there's nothing to match, so it stays put
+        let mut level_match = LevelMatch::new(44100.0);
+        for _ in 0..44100 {
+            assert_eq!(level_match.process((0.5, 0.5), (0.0, 0.0)), 1.0);
+        }
+    }
+
+    #[test]
+    fn mix_lock_doesnt_blow_up_the_first_echo() {
+        let mut delay = SillyDelay::builder().delay_ms(500.0).dry_wet(0.5).build().unwrap();
+        delay.set_parameter(Param::MixLock.to_i32(), 1.0);
+        let mut input = sine(44100.0, 1000.0, 0.5, 44100);
+        input[11025..].iter_mut().for_each(|sample| *sample = 0.0);
+        let (out_l, _) = render(&mut delay, &input, &input);
+        // the echo of the first quarter second comes back no louder than it went in
+        assert!(peak(&out_l[22050..33075]) < 0.5, "{}", peak(&out_l[22050..33075]));
+    }
+
+    #[test]
+    fn saturation_shapes_are_bounded_and_quiet_signals_pass() {
+        for shape in [Saturation::Soft, Saturation::Hard, Saturation::Fold, Saturation::Diode] {
+            assert_eq!(Saturation::from_param(shape.to_param()), shape);
+            for step in -1000..=1000 {
+                let sample = step as f32 / 10.0;
+                assert!(shape.shape(sample).abs() <= 1.0, "{:?} {}", shape, sample);
+            }
+            // a hundredth of full scale comes through within a percent or so
+            for sample in [-0.01, -0.001, 0.001, 0.01] {
+                assert!((shape.shape(sample) / sample - 1.0).abs() < 0.01, "{:?} {}", shape, sample);
+            }
+        }
+        assert_eq!(Saturation::Off.shape(100.0), 100.0);
+    }
+
+    #[test]
+    fn true_stereo_keeps_the_sides_apart() {
+        let left = sine(44100.0, 300.0, 0.8, 8820);
+        let right = sine(44100.0, 2000.0, 0.3, 8820);
+        let silence = vec![0.0; 8820];
+        let build = || {
+            let mut delay = SillyDelay::builder().delay_ms(20.0).feedback(0.9).dry_wet(0.5).build().unwrap();
+            delay.set_parameter(Param::TrueStereo.to_i32(), 1.0);
+            delay.set_parameter(Param::Saturation.to_i32(), Saturation::Soft.to_param());
+            delay.set_parameter(Param::DuckDepth.to_i32(), 1.0);
+            delay.set_parameter(Param::MixLock.to_i32(), 1.0);
+            delay
+        };
+        // whatever's on one side makes no difference at all to the other
+        let (both_l, both_r) = render(&mut build(), &left, &right);
+        let (only_l, none_r) = render(&mut build(), &left, &silence);
+        let (none_l, only_r) = render(&mut build(), &silence, &right);
+        assert_eq!(both_l, only_l);
+        assert_eq!(both_r, only_r);
+        assert!(none_l.iter().chain(&none_r).all(|&sample| sample == 0.0));
+
+        // without it the ducking (for one) listens to both
+        let linked = || {
+            let mut delay = build();
+            delay.set_parameter(Param::TrueStereo.to_i32(), 0.0);
+            delay
+        };
+        assert_ne!(render(&mut linked(), &left, &right).0, render(&mut linked(), &left, &silence).0);
+    }
+
+    #[test]
+    fn mono_output_makes_both_sides_the_sum() {
+        let left = sine(44100.0, 300.0, 0.8, 4410);
+        let right = sine(44100.0, 2000.0, 0.3, 4410);
+        for mode in [MonoOutput::Mono, MonoOutput::LoFi] {
+            let mut delay = SillyDelay::builder().delay_ms(10.0).feedback(0.5).dry_wet(0.5).build().unwrap();
+            delay.set_parameter(Param::MonoOutput.to_i32(), mode.to_param());
+            let (out_l, out_r) = render(&mut delay, &left, &right);
+            assert_eq!(out_l, out_r);
+            assert!(peak(&out_l) > 0.1);
+        }
+
+        // the plain sum is just (left + right) / 2, with nothing else in the way
+        let mut delay = SillyDelay::builder().dry_wet(0.0).build().unwrap();
+        delay.set_parameter(Param::MonoOutput.to_i32(), MonoOutput::Mono.to_param());
+        let (out_l, _) = render(&mut delay, &left, &right);
+        assert!(out_l[500..].iter().zip(&left[500..]).zip(&right[500..])
+            .all(|((out, left), right)| (out - (left + right) * 0.5).abs() < 1e-6));
+    }
+
+    #[test]
+    fn full_resonance_sustains_without_growing() {
+        let mut delay = SillyDelay::builder().delay_ms(50.0).build().unwrap();
+        delay.set_parameter(Param::Resonance.to_i32(), 1.0);
+        // under RESONANCE_KNEE, so the limiter leaves it alone
+        let hit: Vec<f32> = impulse(44100 * 4, 100).iter().map(|sample| sample * 0.4).collect();
+        let (out_l, _) = render(&mut delay, &hit, &hit);
+        // forty times round the loop later, most of it's still there
+        let first = peak(&out_l[..4410]);
+        assert!(first > 0.39);
+        assert!(peak(&out_l[44100 * 2..44100 * 2 + 2205]) > first * 0.9);
+        assert!(peak(&out_l) <= first);
+
+        // and with a loud, steady input piling in on top, the limiter keeps what comes back round under full scale.
+        // So the most the wet can be is that plus the input
+        let input = sine(44100.0, 440.0, 1.0, 44100 * 4);
+        let (out_l, out_r) = render(&mut delay, &input, &input);
+        assert!(peak(&out_l) <= 2.0 && peak(&out_r) <= 2.0, "{}", peak(&out_l));
+        assert!(peak(&out_l[44100 * 3..]) > 0.5);
+    }
+
+    #[test]
+    fn test_signal_plays_with_silent_input() {
+        let silence = vec![0.0; 44100 + 1000];
+        let mut delay = SillyDelay::builder().dry_wet(0.0).build().unwrap();
+        let (out_l, out_r) = render(&mut delay, &silence, &silence);
+        assert_eq!(peak(&out_l), 0.0);
+
+        delay.set_parameter(Param::TestSignal.to_i32(), TestSignal::Tone.to_param());
+        let (out_l, out_r_tone) = render(&mut delay, &silence, &silence);
+        assert!((peak(&out_l) - TEST_SIGNAL_LEVEL).abs() < 0.001);
+        assert_eq!(out_l, out_r_tone);
+        assert_eq!(out_r, silence);
+
+        // a click straight away, then the next one a second later
+        delay.set_parameter(Param::TestSignal.to_i32(), TestSignal::Impulse.to_param());
+        let (out_l, _) = render(&mut delay, &silence, &silence);
+        assert_eq!(first_and_last_above(&out_l, 0.001), (Some(0), Some(44100)));
+        assert_eq!(out_l[0], TEST_SIGNAL_LEVEL);
+    }
+
+    #[test]
+    fn delay_quantize_snaps_to_the_grid() {
+        let mut delay = SillyDelay::default();
+        let grid_param = |grid: f32| QUANTIZE_GRIDS.iter().position(|&each| each == grid).unwrap() as f32 / (QUANTIZE_GRIDS.len() - 1) as f32;
+        let set_ms = |delay: &mut SillyDelay, ms: f32| delay.set_parameter(Param::DelayTime.to_i32(), delay_to_knob(ms / 2000.0));
+        for (grid, ms, snapped) in [(0.0, 123.0, "123.0"), (1.0, 123.4, "123.0"), (10.0, 123.0, "120.0"), (10.0, 126.0, "130.0"),
+            (50.0, 123.0, "100.0"), (100.0, 160.0, "200.0"), (10.0, 3.0, "10.0"), (100.0, 2000.0, "2000.0")] {
+            delay.set_parameter(Param::Quantize.to_i32(), grid_param(grid));
+            set_ms(&mut delay, ms);
+            assert_eq!(delay.get_parameter_text(Param::DelayTime.to_i32()), snapped, "{} {}", grid, ms);
+        }
+
+        // and that's the delay the buffer gets
+        delay.set_parameter(Param::Quantize.to_i32(), grid_param(10.0));
+        set_ms(&mut delay, 123.0);
+        delay.set_sample_rate(44100.0);
+        assert_eq!(delay.delay_buffer.delay(), 120.0 * 44.1);
+        // the knob keeps its place underneath
+        delay.set_parameter(Param::Quantize.to_i32(), 0.0);
+        assert_eq!(delay.get_parameter_text(Param::DelayTime.to_i32()), "123.0");
+    }
+
+    #[test]
+    fn subsonic_filter_cuts_rumble_and_leaves_the_rest() {
+        let level_through = |freq: f32| {
+            let mut delay = SillyDelay::builder().dry_wet(0.0).build().unwrap();
+            delay.set_parameter(Param::Subsonic.to_i32(), 1.0);
+            let (out_l, _) = render(&mut delay, &sine(44100.0, freq, 0.5, 44100 * 2), &sine(44100.0, freq, 0.5, 44100 * 2));
+            // the second second, once it's settled
+            peak(&out_l[44100..]) / 0.5
+        };
+        // 15Hz is under half an octave below the cutoff, where a 2nd order Butterworth is down about 6dB
+        assert!(level_through(15.0) < 0.6, "{}", level_through(15.0));
+        assert!(level_through(5.0) < 0.07, "{}", level_through(5.0));
+        assert!((level_through(1000.0) - 1.0).abs() < 0.001, "{}", level_through(1000.0));
+    }
+
+    #[test]
+    fn stereo_knob_widens_a_mono_input() {
+        let input = sine(44100.0, 500.0, 0.5, 8820);
+        let side_level = |stereo: f32| {
+            let mut delay = SillyDelay::builder().delay_ms(10.0).feedback(0.5).build().unwrap();
+            delay.set_parameter(Param::Stereo.to_i32(), stereo);
+            let (out_l, out_r) = render(&mut delay, &input, &input);
+            let side: Vec<f32> = out_l.iter().zip(&out_r).map(|(l, r)| (l - r) * 0.5).collect();
+            peak(&side[4410..])
+        };
+        assert_eq!(side_level(0.0), 0.0);
+        // the further up, the wider
+        let (some, all) = (side_level(0.5), side_level(1.0));
+        assert!(some > 0.05, "{}", some);
+        assert!(all > some * 1.2, "{} {}", some, all);
+    }
+
+    #[test]
+    fn reset_puts_every_parameter_back() {
+        let mut delay = SillyDelay::builder().delay_ms(100.0).feedback(0.9).build().unwrap();
+        for param in Param::ALL {
+            let away = if param.default() > 0.5 { 0.2 } else { 0.8 };
+            delay.set_parameter(param.to_i32(), away);
+        }
+        let input = sine(44100.0, 500.0, 0.5, 4410);
+        render(&mut delay, &input, &input);
+
+        delay.reset_to_defaults();
+        for param in Param::ALL {
+            assert_eq!(delay.get_parameter(param.to_i32()), param.default(), "{}", param.name());
+        }
+        // and nothing from before is still in the buffers
+        let silence = vec![0.0; 44100];
+        let (out_l, out_r) = render(&mut delay, &silence, &silence);
+        assert!(out_l.iter().chain(&out_r).all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn channel_modes_keep_or_share_the_sides() {
+        let (left, silence) = (impulse(8820, 100), vec![0.0; 8820]);
+        let right_for = |mode: ChannelMode| {
+            let mut delay = SillyDelay::builder().delay_ms(20.0).feedback(0.8).build().unwrap();
+            delay.set_parameter(Param::ChannelMode.to_i32(), mode.to_param());
+            delay.set_sample_rate(44100.0);
+            render(&mut delay, &left, &silence).1
+        };
+        // Dual Mono and Mid/Side both keep a hit on the left out of the right
+        assert!(right_for(ChannelMode::DualMono).iter().all(|&sample| sample == 0.0));
+        assert!(right_for(ChannelMode::MidSide).iter().all(|&sample| sample.abs() < 1e-6));
+        // Cross Linked moves some of it over from the second echo on
+        let crossed = right_for(ChannelMode::CrossLinked);
+        assert!(crossed[..100 + 882 * 2].iter().all(|&sample| sample == 0.0));
+        assert!(crossed[100 + 882 * 2] > 0.01);
+    }
+
+    #[test]
+    fn feedback_tops_out_at_the_ceiling() {
+        assert_eq!(loop_gain(MIN_FEEDBACK), 0.0);
+        assert_eq!(loop_gain(1.0), FEEDBACK_CEILING);
+        let mut delay = SillyDelay::builder().delay_ms(50.0).feedback(1.0).build().unwrap();
+        assert_eq!(delay.feedback_headroom_used(), FEEDBACK_CEILING);
+        // and each echo really is that much quieter than the one before
+        let (out_l, _) = render(&mut delay, &impulse(2205 * 3 + 200, 100), &impulse(2205 * 3 + 200, 100));
+        assert!((out_l[100 + 2205 * 2] / out_l[100 + 2205] - FEEDBACK_CEILING).abs() < 1e-4);
+        assert!((out_l[100 + 2205 * 3] / out_l[100 + 2205 * 2] - FEEDBACK_CEILING).abs() < 1e-4);
+    }
+
+    #[test]
+    fn headroom_used_rises_with_feedback_and_resonance() {
+        let mut delay = SillyDelay::builder().delay_ms(50.0).build().unwrap();
+        let mut last = delay.feedback_headroom_used();
+        assert_eq!(last, 0.0);
+        for feedback in [0.3, 0.6, 1.0] {
+            delay.set_parameter(Param::Feedback.to_i32(), feedback);
+            assert!(delay.feedback_headroom_used() > last);
+            last = delay.feedback_headroom_used();
+        }
+        for resonance in [0.3, 0.6, 1.0] {
+            delay.set_parameter(Param::Resonance.to_i32(), resonance);
+            assert!(delay.feedback_headroom_used() > last);
+            last = delay.feedback_headroom_used();
+        }
+        assert_eq!(last, RESONANCE_CEILING);
+        assert!(last < 1.0);
+    }
+
+    #[test]
+    fn side_only_leaves_a_mono_input_dry() {
+        let build = || {
+            let mut delay = SillyDelay::builder().delay_ms(10.0).feedback(0.8).build().unwrap();
+            delay.set_parameter(Param::SideOnly.to_i32(), 1.0);
+            delay.set_sample_rate(44100.0);
+            delay
+        };
+        let (left, right) = (sine(44100.0, 300.0, 0.5, 8820), sine(44100.0, 700.0, 0.5, 8820));
+        let (out_l, out_r) = render(&mut build(), &left, &left);
+        assert!(out_l.iter().chain(&out_r).all(|&sample| sample.abs() < 1e-6));
+
+        // anything that differs between the sides gets echoed, and only that, so the wet has no mid at all
+        let (out_l, out_r) = render(&mut build(), &left, &right);
+        assert!(peak(&out_l) > 0.1);
+        assert!(out_l.iter().zip(&out_r).all(|(l, r)| (l + r).abs() < 1e-6));
+    }
+
+    #[test]
+    fn morph_goes_between_the_snapshots() {
+        let mut delay = SillyDelay::default();
+        let params = [Param::Feedback, Param::DryWet, Param::Stereo, Param::TremoloDepth];
+        let (a, b) = ([0.2, 0.1, 0.0, 0.4], [0.8, 0.9, 1.0, 0.6]);
+        for (values, snapshot) in [(a, Snapshot::A), (b, Snapshot::B)] {
+            for (param, value) in params.iter().zip(values) {
+                delay.set_parameter(param.to_i32(), value);
+            }
+            delay.store_snapshot(snapshot);
+        }
+        // something that doesn't morph stays wherever it's put
+        delay.set_parameter(Param::Saturation.to_i32(), Saturation::Fold.to_param());
+
+        for (morph, expected) in [(0.0, a), (1.0, b), (0.5, [0.5, 0.5, 0.5, 0.5])] {
+            delay.set_parameter(Param::Morph.to_i32(), morph);
+            for (param, value) in params.iter().zip(expected) {
+                assert!((delay.get_parameter(param.to_i32()) - value).abs() < 1e-6, "{} at {}", param.name(), morph);
+            }
+            assert_eq!(delay.get_parameter(Param::Saturation.to_i32()), Saturation::Fold.to_param());
+        }
+    }
+
+    #[test]
+    fn duck_sources_hear_what_they_say() {
+        let mut input = sine(44100.0, 1000.0, 0.8, 44100 * 2);
+        // playing for the first half second, then the echoes carry on by themselves
+        input[22050..].iter_mut().for_each(|sample| *sample = 0.0);
+        let wet_with = |source: Option<DuckSource>, dry_wet: f32| {
+            let mut delay = SillyDelay::builder().delay_ms(100.0).feedback(0.8).dry_wet(dry_wet).build().unwrap();
+            if let Some(source) = source {
+                delay.set_parameter(Param::DuckDepth.to_i32(), 1.0);
+                delay.set_parameter(Param::DuckSource.to_i32(), source.to_param());
+            }
+            render(&mut delay, &input, &input).0
+        };
+        // once the playing stops (and the release has had a while), only Output is still ducking: it hears its own echoes
+        let tail = |out: &[f32]| peak(&out[44100..]);
+        let plain = wet_with(None, 0.5);
+        for source in [DuckSource::Input, DuckSource::Dry] {
+            assert!((tail(&wet_with(Some(source), 0.5)) / tail(&plain) - 1.0).abs() < 0.01, "{:?}", source);
+        }
+        assert!(tail(&wet_with(Some(DuckSource::Output), 0.5)) < tail(&plain) * 0.9);
+
+        // with Dry/Wet all the way to wet there's no dry to hear, so Dry doesn't duck at all while Input still does
+        let playing = |out: &[f32]| peak(&out[11025..22050]);
+        let plain = wet_with(None, 1.0);
+        assert_eq!(playing(&wet_with(Some(DuckSource::Dry), 1.0)), playing(&plain));
+        assert!(playing(&wet_with(Some(DuckSource::Input), 1.0)) < playing(&plain) * 0.5);
+
+        // settings saved before Dry was added still pick the same thing
+        assert_eq!(DuckSource::from_param(0.5), DuckSource::Output);
+        assert_eq!(DuckSource::from_param(1.0), DuckSource::Sidechain);
+        for source in [DuckSource::Input, DuckSource::Dry, DuckSource::Output, DuckSource::Sidechain] {
+            assert_eq!(DuckSource::from_param(source.to_param()), source);
+        }
+    }
+
+    #[test]
+    fn sidechain_ducks_from_its_own_inputs() {
+        let input = sine(44100.0, 500.0, 0.5, 44100);
+        let mut kick = vec![0.0; 44100];
+        kick[22050..22050 + 2205].copy_from_slice(&sine(44100.0, 60.0, 1.0, 2205));
+        let build = || {
+            let mut delay = SillyDelay::builder().delay_ms(10.0).build().unwrap();
+            delay.set_parameter(Param::DuckDepth.to_i32(), 1.0);
+            delay.set_parameter(Param::DuckSource.to_i32(), DuckSource::Sidechain.to_param());
+            delay
+        };
+
+        let mut delay = build();
+        let (mut out_l, mut out_r) = (vec![0.0; 44100], vec![0.0; 44100]);
+        let inputs = [input.as_ptr(), input.as_ptr(), kick.as_ptr(), kick.as_ptr()];
+        process_raw(&mut delay, &inputs, &mut [out_l.as_mut_ptr(), out_r.as_mut_ptr()], 44100);
+        // the steady input doesn't duck anything, only the kick does
+        assert!((peak(&out_l[11025..22050]) - 0.5).abs() < 0.01);
+        assert!(peak(&out_l[22050 + 1000..22050 + 2205]) < 0.25);
+        assert!((peak(&out_l[44100 - 4410..]) - 0.5).abs() < 0.01);
+
+        // with only two inputs there's no sidechain, so it listens to the input like it always has
+        let (out_l, _) = render(&mut build(), &input, &input);
+        assert!(peak(&out_l[11025..22050]) < 0.5 * 0.6);
+    }
+
+    #[test]
+    fn motion_moves_where_the_loop_reads_from() {
+        let build = |motion: f32| {
+            let mut delay = SillyDelay::builder().delay_ms(50.0).feedback(0.8).build().unwrap();
+            delay.set_parameter(Param::Motion.to_i32(), motion);
+            delay
+        };
+        // a hit two seconds in, once the drift has had time to get going
+        let hit = impulse(44100 * 3, 88200);
+        let (still_l, _) = render(&mut build(0.0), &hit, &hit);
+        assert_eq!(first_and_last_above(&still_l[..88200 + 2205 + 2], 0.01), (Some(88200 + 2205), Some(88200 + 2205)));
+
+        let (out_l, out_r) = render(&mut build(1.0), &hit, &hit);
+        // the first echo comes late by somewhere up to MOTION_MAX_OFFSET, and by different amounts each side
+        let furthest = MOTION_MAX_OFFSET * 44100.0;
+        let first = |out: &[f32]| centre(&out[88200 + 2205 - 10..88200 + 2205 + 150]) + (88200 + 2205 - 10) as f32;
+        let (late_l, late_r) = (first(&out_l) - (88200 + 2205) as f32, first(&out_r) - (88200 + 2205) as f32);
+        assert!(late_l > 0.5 && late_l < furthest, "{}", late_l);
+        assert!(late_r > 0.5 && late_r < furthest, "{}", late_r);
+        assert!((late_l - late_r).abs() > 0.5);
+        // each echo goes round through it again, so the second is later still
+        let second = centre(&out_l[88200 + 4410 - 10..88200 + 4410 + 300]) + (88200 + 4410 - 10) as f32;
+        assert!(second - (88200 + 4410) as f32 > late_l + 0.5);
+
+        // and the seeds are fixed, so it does exactly the same every time
+        assert_eq!(render(&mut build(1.0), &hit, &hit), (out_l, out_r));
+    }
+
+    #[test]
+    fn denormals_never_get_into_the_loop() {
+        for dither in [false, true] {
+            let mut delay = SillyDelay::builder().delay_ms(20.0).feedback(0.9).build().unwrap();
+            delay.set_parameter(Param::Denormals.to_i32(), switch_value(dither));
+            render(&mut delay, &impulse(4410, 100), &impulse(4410, 100));
+            // long enough for the tail to have gone well past anything an f32 can hold
+            let silence = vec![0.0; 44100];
+            let mut tail = (Vec::new(), Vec::new());
+            for _ in 0..20 {
+                tail = render(&mut delay, &silence, &silence);
+                let subnormal = delay.delay_buffer.samples.iter().any(|sample| sample.0.is_subnormal() || sample.1.is_subnormal());
+                assert!(!subnormal, "dither {}", dither);
+            }
+            let (tail_l, tail_r) = tail;
+            assert!(tail_l.iter().chain(&tail_r).all(|sample| !sample.is_subnormal()));
+            if dither {
+                // still there, but down at the noise floor: well under -120dB, and well above denormal
+                assert!(peak(&tail_l) < 1e-6 && peak(&tail_l) > 1e-12, "{}", peak(&tail_l));
+            } else {
+                assert_eq!(peak(&tail_l), 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn pan_rotate_turns_each_echo_further() {
+        let (hit, silence) = (impulse(2205 * 8, 100), vec![0.0; 2205 * 8]);
+        let angles = |pan_rotate: f32| {
+            let mut delay = SillyDelay::builder().delay_ms(50.0).feedback(0.8).build().unwrap();
+            delay.set_parameter(Param::PanRotate.to_i32(), pan_rotate);
+            // all on the left to start with
+            let (out_l, out_r) = render(&mut delay, &hit, &silence);
+            (1..8).map(|echo| out_r[100 + echo * 2205].atan2(out_l[100 + echo * 2205])).collect::<Vec<f32>>()
+        };
+        assert!(angles(0.0).iter().all(|&angle| angle == 0.0));
+        // a quarter of PAN_ROTATE_MAX_STEP is an eighth of a half turn per echo, so by the 5th it's hard right
+        // and after that it carries on round, upside down
+        let step = 0.25 * PAN_ROTATE_MAX_STEP;
+        for (echo, angle) in angles(0.25).into_iter().enumerate() {
+            let expected = (echo as f32 * step + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI;
+            assert!((angle - expected).abs() < 1e-3, "echo {}: {} vs {}", echo + 1, angle, expected);
+        }
+    }
+
+    #[test]
+    fn buffer_size_readouts_match_the_longest_delay() {
+        let mut delay = SillyDelay::default();
+        delay.set_parameter(Param::DelayTime.to_i32(), 1.0);
+        for sample_rate in [22050.0, 44100.0, 48000.0, 96000.0, 192000.0] {
+            delay.set_sample_rate(sample_rate);
+            // two seconds of delay, with Motion's room and a sample either side of it on top
+            let room = (MOTION_MAX_OFFSET * sample_rate).ceil() as usize + 2;
+            assert_eq!(delay.max_buffer_samples(), (sample_rate * 2.0) as usize + room);
+            assert_eq!(delay.buffer_samples(), delay.max_buffer_samples());
+            let expected_ms = 2000.0 + room as f32 / sample_rate * 1000.0;
+            assert!((delay.buffer_ms() - expected_ms).abs() < 0.01, "{}", delay.buffer_ms());
+        }
+
+        // shorter delays only take what they need
+        delay.set_parameter(Param::DelayTime.to_i32(), delay_to_knob(0.05));
+        delay.set_sample_rate(44100.0);
+        assert_eq!(delay.buffer_samples(), 4410 + 133 + 2);
+        assert!(delay.buffer_samples() < delay.max_buffer_samples());
+    }
+
+    #[test]
+    fn delay_knob_middle_is_the_geometric_mean() {
+        let (min, max) = Param::DelayTime.range();
+        assert!((knob_to_delay(0.5) - (min * max).sqrt()).abs() < 1e-6);
+        assert_eq!(knob_to_delay(0.0), min);
+        assert!((knob_to_delay(1.0) - max).abs() < 1e-6);
+        // and it's the same on screen: sqrt(0.1ms * 2000ms)
+        let mut delay = SillyDelay::default();
+        delay.set_parameter(Param::DelayTime.to_i32(), 0.5);
+        assert_eq!(delay.get_parameter_text(Param::DelayTime.to_i32()), "14.1");
+        assert_eq!(delay.get_parameter(Param::DelayTime.to_i32()), 0.5);
+        for step in 0..=10 {
+            let knob = step as f32 / 10.0;
+            assert!((delay_to_knob(knob_to_delay(knob)) - knob).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn settings_from_before_the_log_knobs_keep_their_delay() {
+        let mut delay = SillyDelay::default();
+        assert_eq!(delay.get_info().version, PARAMETER_VERSION);
+        // 250ms and 1000ms on the old straight-line knobs
+        delay.import_settings("# SillyDelay settings\n\"Delay Time\" = 0.125\n\"Delay Time 2\" = 0.5\n\"Dry/Wet\" = 0.125\n").unwrap();
+        assert_eq!(delay.get_parameter_text(Param::DelayTime.to_i32()), "250.0");
+        assert_eq!(delay.get_parameter_text(Param::DelayTime2.to_i32()), "1000.0");
+        assert_eq!(delay.get_parameter(Param::DryWet.to_i32()), 0.125);
+
+        // anything exported now is taken as it is
+        let text = delay.export_settings();
+        assert!(text.starts_with(&format!("# SillyDelay settings v{}\n", PARAMETER_VERSION)));
+        let mut other = SillyDelay::default();
+        other.import_settings(&text).unwrap();
+        assert_eq!(other.get_parameter_text(Param::DelayTime.to_i32()), "250.0");
+        other.import_settings("\"Delay Time\" = 0.5").unwrap();
+        assert_eq!(other.get_parameter_text(Param::DelayTime.to_i32()), "14.1");
+    }
+
+    #[test]
+    fn fully_dry_is_transparent() {
+        let mut delay = SillyDelay::default();
+        delay.set_parameter(Param::DryWet.to_i32(), 0.0);
+        // past the fade in first, which is the one thing that's meant to touch the dry
+        render(&mut delay, &[0.0; 1000], &[0.0; 1000]);
+        let (impulse, tone) = (impulse(4410, 100), sine(44100.0, 1000.0, 0.9, 4410));
+        for (input_l, input_r) in [(&impulse, &tone), (&tone, &impulse)] {
+            let (out_l, out_r) = render(&mut delay, input_l, input_r);
+            assert!(out_l.iter().zip(input_l.iter()).all(|(out, input)| (out - input).abs() < 1e-6));
+            assert!(out_r.iter().zip(input_r.iter()).all(|(out, input)| (out - input).abs() < 1e-6));
+        }
+    }
+
+    #[test]
+    fn interpolation_settings_read_between_samples() {
+        // on a straight line, linear and cubic land exactly on it and none rounds to the nearest sample
+        let (before, newer, older, after) = (1.0, 2.0, 3.0, 4.0);
+        for fraction in [0.0, 0.25, 0.5, 0.75] {
+            assert_eq!(Interpolation::Linear.read(before, newer, older, after, fraction), 2.0 + fraction);
+            assert!((Interpolation::Cubic.read(before, newer, older, after, fraction) - (2.0 + fraction)).abs() < 1e-6);
+        }
+        assert_eq!(Interpolation::None.read(before, newer, older, after, 0.25), 2.0);
+        assert_eq!(Interpolation::None.read(before, newer, older, after, 0.75), 3.0);
+
+        // and on the delay line itself, 22.25 samples
+        let echo = |interpolation: Interpolation| {
+            let mut delay = SillyDelay::builder().delay_ms(22.25 / 44.1).build().unwrap();
+            delay.set_parameter(Param::Interpolation.to_i32(), interpolation.to_param());
+            assert!((delay.delay_buffer.delay() - 22.25).abs() < 1e-3);
+            let (out_l, _) = render(&mut delay, &impulse(1000, 500), &impulse(1000, 500));
+            out_l[500 + 21..500 + 25].to_vec()
+        };
+        assert_eq!(echo(Interpolation::None), [0.0, 1.0, 0.0, 0.0]);
+        let linear = echo(Interpolation::Linear);
+        assert!((linear[1] - 0.75).abs() < 1e-3 && (linear[2] - 0.25).abs() < 1e-3 && linear[0] == 0.0 && linear[3] == 0.0);
+        // cubic reaches one sample further each way, where it rings slightly below 0
+        let cubic = echo(Interpolation::Cubic);
+        assert!(cubic[0] < 0.0 && cubic[3] < 0.0);
+        assert!((cubic.iter().sum::<f32>() - 1.0).abs() < 1e-3);
+        assert!((centre(&linear) - 1.25).abs() < 1e-3);
+    }
+
+    #[test]
+    fn midi_cc_sets_its_parameter() {
+        let mut delay = SillyDelay::default();
+        // on any channel
+        delay.midi_event([0xb0, 91, 0]);
+        assert_eq!(delay.get_parameter(Param::DryWet.to_i32()), 0.0);
+        delay.midi_event([0xb5, 91, 127]);
+        assert_eq!(delay.get_parameter(Param::DryWet.to_i32()), 1.0);
+        delay.midi_event([0xbf, 13, 64]);
+        assert!((delay.get_parameter(Param::Feedback.to_i32()) - 64.0 / 127.0).abs() < 1e-6);
+        delay.midi_event([0xb0, 12, 127]);
+        assert_eq!(delay.get_parameter(Param::DelayTime.to_i32()), 1.0);
+
+        // CCs that aren't mapped, and things that aren't CCs, leave everything alone
+        let before = delay.export_settings();
+        delay.midi_event([0xb0, 1, 100]);
+        delay.midi_event([0xe0, 91, 0]);
+        delay.midi_event([0xa0, 13, 0]);
+        assert_eq!(delay.export_settings(), before);
+    }
+
+    #[test]
+    fn fast_delay_sweeps_share_one_fade() {
+        let mut delay = SillyDelay::builder().delay_ms(100.0).feedback(0.7).build().unwrap();
+        let input = sine(44100.0, 300.0, 0.5, 44100);
+        let mut gains = Vec::new();
+        let mut last_knob = 0.0;
+        for (i, sample) in input.iter().enumerate() {
+            // a new delay time every 32 samples, all over the place, for half a second
+            if i % 32 == 0 && i < 22050 {
+                last_knob = (i as f32 * 0.618).fract();
+                delay.set_parameter(Param::DelayTime.to_i32(), last_knob);
+            }
+            delay.process_block(&[*sample], &[*sample], &mut [0.0], &mut [0.0]);
+            gains.push(delay.clear_gain);
+        }
+        // however many changes pile up, the fade only ever moves one step at a time
+        let step = 1.0 / (CLEAR_FADE_TIME * 44100.0);
+        assert!(gains.windows(2).all(|pair| (pair[1] - pair[0]).abs() <= step * 1.001));
+        // once the first fade is down, the wet stays held down while the changes keep coming, rather than
+        // starting a fresh fade from the top for each one
+        let first_fade = (CLEAR_FADE_TIME * 44100.0) as usize + 1;
+        assert!(gains[first_fade..22050].iter().all(|&gain| gain <= step * 32.0 * 1.001));
+        // and it ends up on the last one, faded all the way back in
+        assert_eq!(delay.delay_buffer.delay(), delay_samples(44100.0, knob_to_delay(last_knob)));
+        assert_eq!(*gains.last().unwrap(), 1.0);
+    }
+
+    // How bright a stretch of signal is: the energy in its sample-to-sample differences over its own energy.
+    // A first difference is a gentle high-pass, so the more top end, the bigger this gets
+    fn brightness(samples: &[f32]) -> f32 {
+        let energy: f32 = samples.iter().map(|sample| sample * sample).sum();
+        let edges: f32 = samples.windows(2).map(|pair| (pair[1] - pair[0]).powi(2)).sum();
+        edges / energy
+    }
+
+    #[test]
+    fn natural_damping_darkens_with_feedback() {
+        // each echo's been through the loop filter once more than the last, so look at the third
+        let echo = |feedback: f32, natural_damping: bool, echo: usize| {
+            let mut delay = SillyDelay::builder().delay_ms(100.0).feedback(feedback).build().unwrap();
+            delay.set_parameter(Param::NaturalDamping.to_i32(), if natural_damping { 1.0 } else { 0.0 });
+            let (out_l, _) = render(&mut delay, &impulse(44100, 1000), &vec![0.0; 44100]);
+            let at = 1000 + echo * 4410;
+            brightness(&out_l[at - 100..at + 400])
+        };
+        let spectra: Vec<f32> = [0.3, 0.6, 0.9].iter().map(|&feedback| echo(feedback, true, 3)).collect();
+        assert!(spectra[0] > spectra[1] && spectra[1] > spectra[2], "{:?}", spectra);
+        // and along one tail, each repeat is darker than the one before
+        assert!(echo(0.9, true, 1) > echo(0.9, true, 2) && echo(0.9, true, 2) > echo(0.9, true, 3));
+        // turned off, feedback doesn't change the colour
+        assert!((echo(0.3, false, 3) - echo(0.9, false, 3)).abs() < 0.001 * echo(0.3, false, 3));
+    }
+
+    #[test]
+    fn age_darkens_and_wobbles() {
+        let aged = |age: f32| {
+            let mut delay = SillyDelay::builder().delay_ms(100.0).build().unwrap();
+            delay.set_parameter(Param::Age.to_i32(), age);
+            delay
+        };
+        let echo_brightness = |age: f32| {
+            let (out_l, _) = render(&mut aged(age), &impulse(44100, 1000), &vec![0.0; 44100]);
+            brightness(&out_l[5410 - 100..5410 + 400])
+        };
+        assert!(echo_brightness(0.0) > echo_brightness(0.5) && echo_brightness(0.5) > echo_brightness(1.0));
+        // wow and flutter: a steady sine comes back with its cycles stretched and squashed
+        let cycle_spread = |age: f32| {
+            let input = sine(44100.0, 441.0, 0.5, 44100 * 2);
+            let (out_l, _) = render(&mut aged(age), &input, &input);
+            // upward zero crossings, placed between samples
+            let crossings: Vec<f32> = (44100..88200)
+                .filter(|&i| out_l[i - 1] < 0.0 && out_l[i] >= 0.0)
+                .map(|i| (i - 44100) as f32 - out_l[i] / (out_l[i] - out_l[i - 1]))
+                .collect();
+            let lengths: Vec<f32> = crossings.windows(2).map(|pair| pair[1] - pair[0]).collect();
+            lengths.iter().fold(0.0f32, |most, &length| most.max(length))
+                - lengths.iter().fold(f32::MAX, |least, &length| least.min(length))
+        };
+        assert!(cycle_spread(0.0) < 0.05, "{}", cycle_spread(0.0));
+        assert!(cycle_spread(1.0) > 0.1, "{}", cycle_spread(1.0));
+    }
+
+    #[test]
+    fn filters_and_smoothing_hold_up_at_192k() {
+        // A Butterworth is 3dB down at its cutoff, and that's where f32 coefficients used to go wrong.
+        // This is the response the stored coefficients actually give, worked out in f64 so it's only them being tested
+        let filter = subsonic_filter(192000.0);
+        let level = |freq: f32| {
+            let w = 2.0 * std::f64::consts::PI * freq as f64 / 192000.0;
+            let (b, a) = ([filter.b0, filter.b1, filter.b2], [1.0, filter.a1, filter.a2]);
+            let response = |c: [f32; 3]| {
+                let re: f64 = (0..3).map(|k| c[k] as f64 * (k as f64 * w).cos()).sum();
+                let im: f64 = (0..3).map(|k| c[k] as f64 * (k as f64 * w).sin()).sum();
+                re.hypot(im)
+            };
+            (response(b) / response(a)) as f32
+        };
+        assert!((level(SUBSONIC_CUTOFF) - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.004, "{}", level(SUBSONIC_CUTOFF));
+        assert!((level(1000.0) - 1.0).abs() < 0.001, "{}", level(1000.0));
+        // a one second smoother moves 1/192000 of the way each sample, near enough
+        let coeff = smoothing_coeff(1.0, 192000.0);
+        assert!((coeff * 192000.0 - 1.0).abs() < 0.0001, "{}", coeff * 192000.0);
+        // and the whole thing runs at the longest delay, with the echo landing where it should
+        let mut delay = SillyDelay::builder().sample_rate(192000.0).delay_ms(2000.0).build().unwrap();
+        let len = 384000 + 2000;
+        let (out_l, out_r) = render(&mut delay, &impulse(len, 1000), &impulse(len, 1000));
+        assert!(out_l.iter().chain(&out_r).all(|sample| sample.is_finite()));
+        assert!((centre(&out_l[1000..]) - 384000.0).abs() < 0.5, "{}", centre(&out_l[1000..]));
+    }
+
+    #[test]
+    fn idle_when_dry_stops_the_tail_or_keeps_it() {
+        for idle_when_dry in [false, true] {
+            let mut delay = SillyDelay::builder().delay_ms(100.0).feedback(0.9).build().unwrap();
+            delay.set_parameter(Param::IdleWhenDry.to_i32(), if idle_when_dry { 1.0 } else { 0.0 });
+            let silence = vec![0.0; 22050];
+            render(&mut delay, &impulse(22050, 1000), &impulse(22050, 1000));
+            // turned all the way dry, the input comes straight through either way
+            delay.set_parameter(Param::DryWet.to_i32(), 0.0);
+            let input = impulse(22050, 1000);
+            let (out_l, _) = render(&mut delay, &input, &input);
+            assert!(out_l.iter().zip(&input).all(|(out, input)| (out - input).abs() < 1e-6));
+            // and back up, the tail's still ringing, unless it was idle, in which case the impulse that went in
+            // while it was dry never got into the buffer either
+            delay.set_parameter(Param::DryWet.to_i32(), 1.0);
+            let (out_l, _) = render(&mut delay, &silence, &silence);
+            if idle_when_dry {
+                assert!(peak(&out_l) < 1e-6, "{}", peak(&out_l));
+            } else {
+                assert!(peak(&out_l) > 0.1, "{}", peak(&out_l));
+            }
+            // and it picks up again from there
+            let (out_l, _) = render(&mut delay, &impulse(22050, 1000), &impulse(22050, 1000));
+            assert!(out_l[5410] > 0.5, "{}", out_l[5410]);
+        }
+    }
+
+    #[test]
+    fn short_delay_guard_turns_short_feedback_down() {
+        // how much of each echo makes it round the loop again, at 5ms (220.5 samples)
+        let ratio = |short_delay_guard: bool| {
+            let mut delay = SillyDelay::builder().delay_ms(5.0).feedback(0.9).build().unwrap();
+            delay.set_parameter(Param::ShortDelayGuard.to_i32(), if short_delay_guard { 1.0 } else { 0.0 });
+            let (out_l, _) = render(&mut delay, &impulse(4410, 1000), &vec![0.0; 4410]);
+            // summed rather than the peak, since reading between samples spreads each echo over a few
+            let echo = |at: usize| out_l[at - 5..at + 5].iter().sum::<f32>();
+            echo(1441) / echo(1220)
+        };
+        let full = loop_gain(0.9);
+        assert!((ratio(false) - full).abs() < 0.01, "{} {}", ratio(false), full);
+        // 5ms is a quarter of SHORT_DELAY_GUARD_TIME
+        assert!((ratio(true) - full * 0.25).abs() < 0.01, "{} {}", ratio(true), full * 0.25);
+        // and from SHORT_DELAY_GUARD_TIME up it leaves the feedback alone
+        let mut delay = SillyDelay::builder().delay_ms(20.0).build().unwrap();
+        delay.set_parameter(Param::ShortDelayGuard.to_i32(), 1.0);
+        assert_eq!(delay.short_delay_gain(), 1.0);
+    }
+
+    #[test]
+    fn detents_are_in_range_and_land_on_the_notable_value() {
+        for &param in Param::ALL.iter() {
+            if let Some(detent) = param.detent() {
+                assert!((0.0..=1.0).contains(&detent), "{}", param.name());
+            }
+        }
+        let mut delay = SillyDelay::default();
+        let text_at_detent = |delay: &mut SillyDelay, param: Param| {
+            delay.set_parameter(param.to_i32(), param.detent().unwrap());
+            delay.get_parameter_text(param.to_i32())
+        };
+        assert_eq!(text_at_detent(&mut delay, Param::DryWet), "50");
+        assert_eq!(text_at_detent(&mut delay, Param::InputTrim), "0.0");
+        assert_eq!(text_at_detent(&mut delay, Param::OutputTrim), "0.0");
+        assert_eq!(text_at_detent(&mut delay, Param::DryPan), "C");
+        assert_eq!(text_at_detent(&mut delay, Param::CorrelationFloor), "0.00");
+        assert_eq!(text_at_detent(&mut delay, Param::Morph), "50");
+        text_at_detent(&mut delay, Param::Feedback);
+        assert_eq!(delay.current_loop_gain(), 0.0);
+        // switches have nothing in between to stop at
+        assert_eq!(Param::Freeze.detent(), None);
+    }
+
+    #[test]
+    fn looper_records_plays_and_overdubs() {
+        // at 1kHz a loop of 100 samples, with 10 more run on for the crossfade
+        let mut looper = Looper::new(1000.0);
+        let (a, b) = (sine(1000.0, 50.0, 0.4, 100), sine(1000.0, 40.0, 0.3, 100));
+        looper.trigger(100);
+        // while it records, what's already going out goes out untouched
+        for i in 0..109 {
+            assert_eq!(looper.process((a[i % 100], -a[i % 100]), (0.3, 0.2), false, 1.0), (0.3, 0.2));
+        }
+        // the last sample of the run-on finishes it, and it starts playing from the top in the same sample.
+        // The sine goes round a whole number of times, so the crossfade leaves it as it was
+        looper.process((a[9], -a[9]), (0.0, 0.0), false, 1.0);
+        let played: Vec<(f32, f32)> = (1..300).map(|_| looper.process((0.0, 0.0), (0.0, 0.0), false, 1.0)).collect();
+        for (i, &(left, right)) in (1..300).zip(&played).skip(100) {
+            assert!((left - a[i % 100]).abs() < 1e-6 && (right + a[i % 100]).abs() < 1e-6, "{}", i);
+        }
+        // one time round with overdub on adds the new input in, to be heard from the next time round
+        for i in 0..100 {
+            let (left, _) = looper.process((b[i], b[i]), (0.0, 0.0), true, 1.0);
+            assert!((left - a[i]).abs() < 1e-6);
+        }
+        for i in 0..100 {
+            let (left, right) = looper.process((0.0, 0.0), (0.0, 0.0), false, 0.5);
+            assert!((left - (a[i] + b[i]) * 0.5).abs() < 1e-6 && (right - (b[i] - a[i]) * 0.5).abs() < 1e-6);
+        }
+
+        // something that doesn't fit the loop a whole number of times still goes round without a click
+        let c = sine(1000.0, 33.0, 0.5, 110);
+        let biggest_step = c.windows(2).fold(0.0f32, |most, pair| most.max((pair[1] - pair[0]).abs()));
+        let mut looper = Looper::new(1000.0);
+        looper.trigger(100);
+        for &sample in &c {
+            looper.process((sample, sample), (0.0, 0.0), false, 1.0);
+        }
+        let played: Vec<f32> = (0..300).map(|_| looper.process((0.0, 0.0), (0.0, 0.0), false, 1.0).0).collect();
+        // the end of the loop to its start, the first time round's already going at full level
+        let step_at_loop_point = (played[199] - played[198]).abs();
+        assert!(step_at_loop_point <= biggest_step * 1.05, "{} {}", step_at_loop_point, biggest_step);
+    }
+
+    #[test]
+    fn post_mix_feedback_sends_the_dry_round_again() {
+        let echoes = |post_mix: bool| {
+            let mut delay = SillyDelay::builder().delay_ms(100.0).feedback(0.6).dry_wet(0.5).build().unwrap();
+            delay.set_parameter(Param::FeedbackTap.to_i32(), if post_mix { 1.0 } else { 0.0 });
+            render(&mut delay, &impulse(22050, 1000), &impulse(22050, 1000)).0
+        };
+        let (pre, post) = (echoes(false), echoes(true));
+        // the first echo is the input either way
+        assert!((pre[5410] - post[5410]).abs() < 1e-6);
+        // Post-Mix takes the mix from a sample later, and the dry in it comes round a delay after that: as loud
+        // as the mix had it (half), times the loop gain, and then half of that in the mix again as wet.
+        // Pre-Mix only ever sends the echo round
+        let dry_again = 0.5 * loop_gain(0.6) * 0.5;
+        assert!((post[5411] - dry_again).abs() < 0.01 * dry_again, "{} {}", post[5411], dry_again);
+        assert!(pre[5411].abs() < 0.001, "{}", pre[5411]);
+    }
+
+    #[test]
+    fn default_works_without_any_setup() {
+        // nothing but process, the way a minimal host would: the default 2ms, 88.2 samples at 44.1k
+        let mut delay = SillyDelay::default();
+        let (input, mut out_l, mut out_r) = (impulse(2000, 1000), vec![0.0; 2000], vec![0.0; 2000]);
+        process_raw(&mut delay, &[input.as_ptr(), input.as_ptr()], &mut [out_l.as_mut_ptr(), out_r.as_mut_ptr()], 2000);
+        assert_eq!(delay.buffer_samples(), DelayLine::size_for(88.2 + MOTION_MAX_OFFSET * 44100.0));
+        assert!((centre(&out_l) - 1088.2).abs() < 0.01, "{}", centre(&out_l));
+        assert_eq!(out_l, out_r);
+        // and every filter a parameter tunes is tuned already, rather than all zeros (silence)
+        let delay = SillyDelay::default();
+        for filter in [&delay.phase_rotator, &delay.damping_l, &delay.damping_r, &delay.age_filter_l, &delay.age_filter_r] {
+            assert!(filter.b0 != 0.0 && filter.a1 != 0.0);
+        }
+    }
+
+    #[cfg(feature = "f64-feedback")]
+    #[test]
+    fn f64_feedback_keeps_a_long_tail_cleaner_than_f32() {
+        // 10ms at 48k is exactly 480 samples, and Feedback all the way up takes 200 trips round to die away
+        let (len, at, period) = (1000 + 480 * 201, 1000, 480);
+        let mut input = vec![0.0; len];
+        input[at..at + 100].copy_from_slice(&sine(48000.0, 1234.5, 0.5, 100));
+        let mut delay = SillyDelay::builder().sample_rate(48000.0).delay_ms(10.0).feedback(1.0).build().unwrap();
+        let (out_l, _) = render(&mut delay, &input, &input);
+
+        // the same loop worked out exactly (near enough, in f64), and with everything rounded to f32 every time round
+        let gain = loop_gain(1.0);
+        let (mut exact, mut rounded) = (vec![0.0f64; len], vec![0.0f32; len]);
+        for i in period..len {
+            exact[i] = input[i - period] as f64 + exact[i - period] * gain as f64;
+            rounded[i] = input[i - period] + rounded[i - period] * gain;
+        }
+        // how far off the 200th echo is, next to how loud it is
+        let last_echo = at + period * 200..at + period * 200 + 100;
+        let error = |output: &dyn Fn(usize) -> f64| {
+            let wrong: f64 = last_echo.clone().map(|i| (output(i) - exact[i]).powi(2)).sum();
+            let level: f64 = last_echo.clone().map(|i| exact[i].powi(2)).sum();
+            (wrong / level).sqrt()
+        };
+        let (f64_error, f32_error) = (error(&|i| out_l[i] as f64), error(&|i| rounded[i] as f64));
+        assert!(f64_error * 4.0 < f32_error, "{} {}", f64_error, f32_error);
+    }
+
+    #[test]
+    fn note_delay_tunes_the_loop_to_the_note() {
+        let mut delay = SillyDelay::default();
+        delay.set_parameter(Param::NoteDelay.to_i32(), 1.0);
+        // the new delay comes in once the old buffer has faded out
+        let settle = |delay: &mut SillyDelay| { render(delay, &[0.0; 4410], &[0.0; 4410]); };
+        let knob = delay.delay_buffer.delay();
+        // A4 is 440Hz, 100.23 samples at 44.1k
+        delay.midi_event([0x90, 69, 100]);
+        settle(&mut delay);
+        assert!((delay.delay_buffer.delay() - 44100.0 / 440.0).abs() < 0.001, "{}", delay.delay_buffer.delay());
+        let (out_l, _) = render(&mut delay, &impulse(2000, 1000), &impulse(2000, 1000));
+        assert!((centre(&out_l) - (1000.0 + 44100.0 / 440.0)).abs() < 0.01, "{}", centre(&out_l));
+        // four octaves up is still in tune, even though it's only a few samples
+        delay.midi_event([0x90, 117, 100]);
+        settle(&mut delay);
+        assert!((delay.delay_buffer.delay() - 44100.0 / 7040.0).abs() < 0.001, "{}", delay.delay_buffer.delay());
+        // letting go of the newest goes back to the one still held, then to the knob
+        delay.midi_event([0x80, 117, 0]);
+        settle(&mut delay);
+        assert!((delay.delay_buffer.delay() - 44100.0 / 440.0).abs() < 0.001);
+        delay.midi_event([0x90, 69, 0]);
+        settle(&mut delay);
+        assert_eq!(delay.delay_buffer.delay(), knob);
+    }
+
+    #[cfg(feature = "cpu-usage")]
+    #[test]
+    fn cpu_usage_is_the_share_of_real_time() {
+        // 5ms of work for every 10ms of audio is 50%, once it's settled
+        let mut meter = CpuMeter::default();
+        for _ in 0..1000 {
+            meter.update(std::time::Duration::from_millis(5), 441, 44100.0);
+        }
+        assert!((meter.usage - 50.0).abs() < 0.01, "{}", meter.usage);
+        // and the plugin's own reading of a few seconds of plain delay is something, but well under real time
+        let mut delay = SillyDelay::builder().delay_ms(300.0).feedback(0.6).build().unwrap();
+        assert_eq!(delay.cpu_usage(), 0.0);
+        let input = sine(44100.0, 440.0, 0.5, 512);
+        for _ in 0..400 {
+            render(&mut delay, &input, &input);
+        }
+        assert!(delay.cpu_usage() > 0.0 && delay.cpu_usage() < 100.0, "{}", delay.cpu_usage());
+    }
+
+    #[test]
+    fn output_routing_puts_dry_and_wet_where_it_says() {
+        let routed = |routing: OutputRouting| {
+            let mut delay = SillyDelay::builder().delay_ms(100.0).dry_wet(0.5).build().unwrap();
+            delay.set_parameter(Param::OutputRouting.to_i32(), routing.to_param());
+            assert_eq!(delay.output_routing, routing);
+            let (input_l, input_r): (Vec<f32>, Vec<f32>) =
+                impulse(8000, 1000).iter().map(|&sample| (sample * 0.8, sample * 0.4)).unzip();
+            let (out_l, out_r) = render(&mut delay, &input_l, &input_r);
+            // the dry at 1000 and the echo at 5410, on each side
+            ((out_l[1000], out_l[5410]), (out_r[1000], out_r[5410]))
+        };
+        let close = |(a, b): (f32, f32), (c, d): (f32, f32)| (a - c).abs() < 1e-6 && (b - d).abs() < 1e-6;
+        // normal stereo: each side its own dry and its own echo, half and half
+        let (left, right) = routed(OutputRouting::Stereo);
+        assert!(close(left, (0.4, 0.4)) && close(right, (0.2, 0.2)), "{:?} {:?}", left, right);
+        // split: one side only the dry and the other only the wet, both summed to mono at the level Dry/Wet gives
+        let (left, right) = routed(OutputRouting::DryLeftWetRight);
+        assert!(close(left, (0.3, 0.0)) && close(right, (0.0, 0.3)), "{:?} {:?}", left, right);
+        let (left, right) = routed(OutputRouting::WetLeftDryRight);
+        assert!(close(left, (0.0, 0.3)) && close(right, (0.3, 0.0)), "{:?} {:?}", left, right);
+    }
+
+    #[test]
+    fn frozen_loop_goes_round_without_a_click() {
+        // 333Hz doesn't fit 100ms a whole number of times, so the newest sample in the buffer is nothing like the oldest
+        let mut delay = SillyDelay::builder().delay_ms(100.0).build().unwrap();
+        let input = sine(44100.0, 333.0, 0.5, 44100 * 3);
+        let biggest_step = input.windows(2).fold(0.0f32, |most, pair| most.max((pair[1] - pair[0]).abs()));
+        render(&mut delay, &input[..44100], &input[..44100]);
+        delay.set_parameter(Param::Freeze.to_i32(), 1.0);
+        // what comes in while it's frozen doesn't get in, it's the same loop over and over
+        let (out_l, _) = render(&mut delay, &input[44100..], &input[44100..]);
+        let frozen = &out_l[8820..];
+        let steps = frozen.windows(2).fold(0.0f32, |most, pair| most.max((pair[1] - pair[0]).abs()));
+        assert!(steps <= biggest_step * 1.05, "{} {}", steps, biggest_step);
+        assert!(frozen.chunks(4410).all(|loop_| peak(loop_) > 0.4), "{}", peak(frozen));
+        assert!(frozen[..4410].iter().zip(&frozen[4410..8820]).all(|(a, b)| (a - b).abs() < 1e-4));
+
+        // Between two samples, 22.25 here, the loop goes round at 22 and is read back exactly, so it doesn't
+        // dull or smear a little more every time round, whatever the wet's read with. The tone fits 22 samples,
+        // so the freeze's fade (many times round a loop this short) lines up with itself
+        let input = sine(44100.0, 44100.0 / 22.0, 0.5, 8820);
+        for interpolation in [Interpolation::Linear, Interpolation::Cubic] {
+            let mut delay = SillyDelay::builder().delay_ms(22.25 / 44.1).build().unwrap();
+            delay.set_parameter(Param::Interpolation.to_i32(), interpolation.to_param());
+            render(&mut delay, &input[..4410], &input[..4410]);
+            delay.set_parameter(Param::Freeze.to_i32(), 1.0);
+            let (out_l, _) = render(&mut delay, &input[4410..], &input[4410..]);
+            let (loop_n, loop_n_50) = (&out_l[2200..2222], &out_l[2200 + 50 * 22..2222 + 50 * 22]);
+            assert!(peak(loop_n) > 0.4, "{:?} {}", interpolation, peak(loop_n));
+            assert!(loop_n.iter().zip(loop_n_50).all(|(a, b)| (a - b).abs() < 1e-6), "{:?}", interpolation);
+        }
+    }
+
+    #[test]
+    fn resending_lookahead_keeps_what_is_in_it() {
+        let input = sine(44100.0, 300.0, 0.5, 8820);
+        let run = |resend: bool| {
+            let mut delay = SillyDelay::builder().delay_ms(50.0).dry_wet(0.5).build().unwrap();
+            delay.set_parameter(Param::Lookahead.to_i32(), 0.5);
+            let (mut out_l, _) = render(&mut delay, &input[..4410], &input[..4410]);
+            // a host sending the same value again, as it might every block
+            if resend {
+                delay.set_parameter(Param::Lookahead.to_i32(), 0.5);
+            }
+            out_l.extend(render(&mut delay, &input[4410..], &input[4410..]).0);
+            out_l
+        };
+        assert_eq!(run(true), run(false));
+    }
+
+    #[test]
+    fn delay_time_2_only_clears_when_it_matters() {
+        let tail = |resend: Option<f32>, network: bool| {
+            let mut delay = SillyDelay::builder().delay_ms(50.0).feedback(0.8).build().unwrap();
+            delay.set_parameter(Param::Network.to_i32(), if network { 1.0 } else { 0.0 });
+            delay.set_parameter(Param::DelayTime2.to_i32(), 0.6);
+            let (mut out_l, _) = render(&mut delay, &impulse(4410, 1000), &impulse(4410, 1000));
+            if let Some(value) = resend {
+                delay.set_parameter(Param::DelayTime2.to_i32(), value);
+            }
+            out_l.extend(render(&mut delay, &[0.0; 8820], &[0.0; 8820]).0);
+            (out_l, delay)
+        };
+        // the same value again, as a host might send every block, changes nothing
+        assert_eq!(tail(Some(0.6), true).0, tail(None, true).0);
+        // that includes the default, before anything's been set
+        let mut delay = SillyDelay::default();
+        delay.set_parameter(Param::Network.to_i32(), 1.0);
+        delay.set_parameter(Param::DelayTime2.to_i32(), Param::DelayTime2.default());
+        assert!(delay.pending_buffers.is_none());
+        // with Network off it isn't heard, so a new value doesn't fade the wet either, but it's ready for when it's on
+        let (out_l, mut delay) = tail(Some(0.7), false);
+        assert_eq!(out_l, tail(None, false).0);
+        assert!(delay.pending_buffers.is_none());
+        delay.set_parameter(Param::Network.to_i32(), 1.0);
+        assert_eq!(delay.network_buffer.delay(), delay_samples(44100.0, knob_to_delay(0.7)));
+        // and with it on, a new value does clear
+        let (_, delay) = tail(Some(0.7), true);
+        assert_eq!(delay.network_buffer.delay(), delay_samples(44100.0, knob_to_delay(0.7)));
+    }
+
+    #[test]
+    fn resending_attack_doesnt_drop_the_wet() {
+        // a steady tone going round, so there's nothing that looks like a new echo once it's going
+        let input = sine(44100.0, 300.0, 0.5, 44100);
+        let run = |resend: bool| {
+            let mut delay = SillyDelay::builder().delay_ms(50.0).feedback(0.6).build().unwrap();
+            delay.set_parameter(Param::Attack.to_i32(), 0.5);
+            let (mut out_l, _) = render(&mut delay, &input[..22050], &input[..22050]);
+            // the same value again, as a host or Morph might send it
+            if resend {
+                delay.set_parameter(Param::Attack.to_i32(), 0.5);
+            }
+            out_l.extend(render(&mut delay, &input[22050..], &input[22050..]).0);
+            out_l
+        };
+        assert_eq!(run(true), run(false));
+    }
+
+    #[test]
+    fn scatter_brings_each_repeat_back_early_by_a_different_amount() {
+        let build = |scatter: f32, seed: f32| {
+            let mut delay = SillyDelay::builder().delay_ms(50.0).build().unwrap();
+            delay.set_parameter(Param::Scatter.to_i32(), scatter);
+            delay.set_parameter(Param::ScatterSeed.to_i32(), seed);
+            delay
+        };
+        // a hit every 200ms, each of which comes back once
+        let mut hits = vec![0.0; 8820 * 20];
+        for at in (0..hits.len()).step_by(8820) {
+            hits[at] = 1.0;
+        }
+        let (plain, _) = render(&mut SillyDelay::builder().delay_ms(50.0).build().unwrap(), &hits, &hits);
+
+        // at 0% it's an ordinary delay, whatever the seed
+        assert_eq!(render(&mut build(0.0, 0.7), &hits, &hits).0, plain);
+
+        let (out_l, out_r) = render(&mut build(1.0, 0.0), &hits, &hits);
+        let mut lates = Vec::new();
+        for at in (0..hits.len()).step_by(8820) {
+            // anywhere from half the delay early to right on time, and never past it
+            let echo = &out_l[at + 1..at + 8820];
+            let (first, last) = first_and_last_above(echo, 0.01);
+            assert!(first.unwrap() + 1 >= 2205 / 2 - 1 && last.unwrap() < 2205, "{:?}", (first, last));
+            lates.push((centre(echo) + 1.0).round() as usize);
+        }
+        lates.dedup();
+        assert!(lates.len() > 10, "{:?}", lates);
+
+        // the same seed scatters the same way every time, and another seed doesn't
+        assert_eq!(render(&mut build(1.0, 0.0), &hits, &hits), (out_l.clone(), out_r));
+        assert_ne!(render(&mut build(1.0, 0.5), &hits, &hits).0, out_l);
+    }
+
+    #[test]
+    fn buffer_snapshot_fills_whatever_it_is_given() {
+        let mut delay = SillyDelay::builder().delay_ms(50.0).build().unwrap();
+        let hit = impulse(1000, 100);
+        render(&mut delay, &hit, &hit);
+
+        // 2205 samples of delay, 5 to a point. The hit went in 900 samples ago, so it's 1305 from the oldest
+        let mut out = vec![f32::NAN; 441];
+        delay.buffer_snapshot(&mut out);
+        assert_eq!(out.iter().position(|&sample| sample != 0.0), Some(1305 / 5));
+        assert_eq!(out.iter().filter(|&&sample| sample != 0.0).count(), 1);
+        assert_eq!(peak(&out), 1.0);
+
+        // one point for each sample, and more points than samples
+        for points in [2205, 10000] {
+            let mut out = vec![f32::NAN; points];
+            delay.buffer_snapshot(&mut out);
+            assert!(out.iter().all(|sample| sample.is_finite()));
+            assert_eq!(peak(&out), 1.0);
+            // the first point that starts at the hit
+            assert_eq!(out.iter().position(|&sample| sample != 0.0), Some((1305 * points).div_ceil(2205)));
+        }
+        // and no points at all
+        delay.buffer_snapshot(&mut []);
+    }
+
+    #[test]
+    fn delay_in_hz_rings_at_that_pitch() {
+        // 14.7 samples, where a whole number of samples would be 35 cents out
+        let freq = 3000.0;
+        let mut delay = SillyDelay::builder().feedback(1.0).build().unwrap();
+        delay.set_parameter(Param::DelayInHz.to_i32(), 1.0);
+        delay.set_parameter(Param::DelayTime.to_i32(), log_to_lin(freq, TUNED_LOWEST, TUNED_HIGHEST));
+        assert_eq!(delay.get_parameter_text(Param::DelayTime.to_i32()), "3000.0 (F#7)");
+        assert_eq!(delay.get_parameter_label(Param::DelayTime.to_i32()), "Hz");
+
+        // after the Clear's fade, so the hit goes into the new buffer
+        let hit = impulse(44100, 2000);
+        let (out, _) = render(&mut delay, &hit, &hit);
+        assert_eq!(delay.buffer_samples(), DelayLine::size_for(14.7 + MOTION_MAX_OFFSET * 44100.0));
+        let out = &out[2000..];
+        // how much of a frequency is in the ring, to the nearest cent within half a semitone either side
+        let level = |freq: f32| {
+            let (re, im) = out.iter().enumerate().fold((0.0, 0.0), |(re, im), (at, &sample)| {
+                let phase = 2.0 * std::f32::consts::PI * freq * at as f32 / 44100.0;
+                (re + sample * phase.cos(), im + sample * phase.sin())
+            });
+            re * re + im * im
+        };
+        let loudest = (-50i32..=50).max_by(|&a, &b| {
+            level(freq * 2f32.powf(a as f32 / 1200.0)).total_cmp(&level(freq * 2f32.powf(b as f32 / 1200.0)))
+        });
+        assert!(loudest.unwrap().abs() <= 3, "{:?}", loudest);
+
+        // back to ms, and the knob's a delay time again
+        delay.set_parameter(Param::DelayInHz.to_i32(), 0.0);
+        assert_eq!(delay.get_parameter_label(Param::DelayTime.to_i32()), "ms");
+        render(&mut delay, &hit, &hit);
+        assert_ne!(delay.buffer_samples(), DelayLine::size_for(14.7 + MOTION_MAX_OFFSET * 44100.0));
+    }
+
+    #[test]
+    fn string_mode_plucks_a_decaying_tuned_tone() {
+        // 200.45 samples, less the low-pass's lag, which has to be made up for to stay in tune
+        let freq = 220.0;
+        let mut delay = SillyDelay::builder().build().unwrap();
+        delay.set_parameter(Param::StringMode.to_i32(), 1.0);
+        delay.set_parameter(Param::DelayInHz.to_i32(), 1.0);
+        delay.set_parameter(Param::DelayTime.to_i32(), log_to_lin(freq, TUNED_LOWEST, TUNED_HIGHEST));
+        // after the Clear's fade, so the hit goes into the new buffer
+        let hit = impulse(44100 * 2, 2000);
+        let (out, _) = render(&mut delay, &hit, &hit);
+        let out = &out[2000..];
+
+        // it rings on for seconds, getting quieter and darker as it goes
+        let (early, late) = (&out[4410..13230], &out[66150..75000]);
+        assert!(peak(late) > 0.001 && peak(late) < peak(early) * 0.5, "{} {}", peak(early), peak(late));
+        assert!(brightness(late) < brightness(early) * 0.5);
+
+        // and at the pitch it's tuned to, within a few cents
+        let level = |freq: f32| {
+            let (re, im) = late.iter().enumerate().fold((0.0, 0.0), |(re, im), (at, &sample)| {
+                let phase = 2.0 * std::f32::consts::PI * freq * at as f32 / 44100.0;
+                (re + sample * phase.cos(), im + sample * phase.sin())
+            });
+            re * re + im * im
+        };
+        let cents = |cents: i32| freq * 2f32.powf(cents as f32 / 1200.0);
+        let loudest = (-50i32..=50).max_by(|&a, &b| level(cents(a)).total_cmp(&level(cents(b))));
+        assert!(loudest.unwrap().abs() <= 3, "{:?}", loudest);
+    }
+}